@@ -0,0 +1,37 @@
+//! Measures binding and building a query with a 1MB string argument, which
+//! should not depend on the size of the string, as it is never copied. Run
+//! with `cargo bench --bench args`.
+use std::{hint::black_box, time::Instant};
+
+use esql::{query, ArgFormat};
+
+const SIZE: usize = 1024 * 1024;
+const ITERATIONS: u32 = 100_000;
+
+fn main() {
+    let document = "x".repeat(SIZE);
+
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        let q = query("SELECT * FROM documents").wh(("body = ?", black_box(document.as_str())));
+        black_box(q.build(ArgFormat::Indexed));
+    }
+
+    println!(
+        "bind and build a {SIZE} byte string: {:?} per query",
+        start.elapsed() / ITERATIONS
+    );
+
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS / 100 {
+        let q = query("SELECT * FROM documents").wh(("body = ?", black_box(document.clone())));
+        black_box(q.build(ArgFormat::Indexed));
+    }
+
+    println!(
+        "same with an owned copy, for comparison: {:?} per query",
+        start.elapsed() / (ITERATIONS / 100)
+    );
+}
@@ -0,0 +1,46 @@
+//! Measures the deserialization of a wide row. Run with
+//! `cargo bench --all-features` against the database configured via
+//! `DATABASE_URL`.
+use std::{collections::HashMap, hint::black_box, time::Instant};
+
+use esql::{query, PgQueryExt, Query};
+use tokio_postgres::{Client, NoTls, Row};
+
+const COLUMNS: usize = 40;
+const ITERATIONS: u32 = 100_000;
+
+fn deserialize<'a, S>(_: &Query<'a, S>, row: &Row) -> HashMap<String, i32>
+where
+    Query<'a, S>: PgQueryExt<'a, Client>,
+{
+    <Query<'a, S> as PgQueryExt<'a, Client>>::deserialize_row(row).unwrap()
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let url = std::env::var("DATABASE_URL")
+        .unwrap_or_else(|_| String::from("host=localhost user=postgres"));
+    let (client, connection) = tokio_postgres::connect(&url, NoTls).await.unwrap();
+
+    tokio::spawn(connection);
+
+    let columns = (0..COLUMNS)
+        .map(|i| format!("{i} AS c{i}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let sql = format!("SELECT {columns}");
+    let row = client.query_one(&sql, &[]).await.unwrap();
+    let q = query("SELECT 1");
+
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        black_box(deserialize(&q, black_box(&row)));
+    }
+
+    println!(
+        "deserialize {COLUMNS} columns: {:?} per row",
+        start.elapsed() / ITERATIONS
+    );
+}
@@ -1,19 +1,23 @@
-use std::{fmt::Display, future::Future, pin::pin};
+use std::{
+    collections::HashMap, fmt::Display, future::Future, hash::Hash, pin::pin, time::Duration,
+};
 
-use futures_util::StreamExt as _;
+use futures_util::{SinkExt as _, StreamExt as _, TryStreamExt as _};
 use qp_postgres::PgPool;
 use serde::Deserialize;
 use tokio_postgres::{
+    error::SqlState,
     row::RowIndex,
     tls::{MakeTlsConnect, TlsConnect},
-    types::{private::BytesMut, FromSqlOwned, ToSql},
-    Client, Row, RowStream, Socket, Transaction,
+    types::{accepts, private::BytesMut, to_sql_checked, FromSql, FromSqlOwned, IsNull, ToSql},
+    CancelToken, Client, CopyOutStream, GenericClient, NoTls, Row, RowStream, Socket, Transaction,
 };
 
 use crate::{
-    query::{ArgFormat, Query},
+    observer::observe,
+    query::{ArgFormat, Query, Trusted},
     serde::{Error, PgRow},
-    Type,
+    Interval, Type,
 };
 
 impl ToSql for Type<'_> {
@@ -25,29 +29,78 @@ impl ToSql for Type<'_> {
     where
         Self: Sized,
     {
+        use tokio_postgres::types::Type as PgType;
+
+        // Integers are converted to the type the server expects, as long as
+        // the value fits, e.g. for an `i32` compared with a `bigint` column.
+        if let Some(value) = self.integer() {
+            match *ty {
+                PgType::INT2 => return i16::try_from(value)?.to_sql(ty, out),
+                PgType::INT4 => return i32::try_from(value)?.to_sql(ty, out),
+                PgType::INT8 => return i64::try_from(value)?.to_sql(ty, out),
+                PgType::NUMERIC => return numeric_to_sql(value, out),
+                _ => {}
+            }
+        }
+
+        if let (Type::Float(a), &PgType::FLOAT8) = (self, ty) {
+            return f64::from(*a).to_sql(ty, out);
+        }
+
         match self {
             Type::Bool(a) => a.to_sql(ty, out),
             Type::Int8(a) => a.to_sql(ty, out),
             Type::Int16(a) => a.to_sql(ty, out),
             Type::Int32(a) => a.to_sql(ty, out),
             Type::Int64(a) => a.to_sql(ty, out),
+            // isize is at most 64 bits wide, so this never truncates, not even
+            // on 32-bit targets. Reading it back works via the INT4/INT8 arms.
             Type::Isize(a) => (*a as i64).to_sql(ty, out),
             Type::UInt8(a) => (*a as i16).to_sql(ty, out),
             Type::UInt16(a) => (*a as u32).to_sql(ty, out),
             Type::UInt32(a) => a.to_sql(ty, out),
-            Type::UInt64(a) => (*a as u32).to_sql(ty, out),
-            Type::Usize(a) => (*a as u32).to_sql(ty, out),
+            Type::UInt64(a) => i64::try_from(*a)?.to_sql(ty, out),
+            Type::Usize(a) => i64::try_from(*a)?.to_sql(ty, out),
             Type::Float(a) => a.to_sql(ty, out),
             Type::Double(a) => a.to_sql(ty, out),
-            Type::Null => None::<Option<bool>>.to_sql(ty, out),
+            Type::Null | Type::TypedNull(_) => None::<Option<bool>>.to_sql(ty, out),
             Type::String(a) => a.to_sql(ty, out),
+            Type::Bytes(a) => a.as_ref().to_sql(ty, out),
+            Type::SystemTime(a) => a.to_sql(ty, out),
+            Type::Array(a) => a.to_sql(ty, out),
+            Type::Secret(a) => a.expose().to_sql(ty, out),
+            Type::Invalid(message) => Err(message.clone().into()),
+
+            #[cfg(feature = "decimal")]
+            Type::Decimal(a) => a.to_sql(ty, out),
+
+            Type::Interval(a) => a.to_sql(ty, out),
 
             #[cfg(feature = "serde-json")]
             Type::Json(a) => a.to_sql(ty, out),
 
+            #[cfg(feature = "serde-json")]
+            Type::JsonRaw(a) => {
+                // The binary jsonb format is the JSON text behind a version byte.
+                if *ty == PgType::JSONB {
+                    out.extend_from_slice(&[1]);
+                }
+
+                out.extend_from_slice(a);
+                Ok(IsNull::No)
+            }
+
             #[cfg(feature = "time")]
             Type::OffsetDateTime(a) => a.to_sql(ty, out),
 
+            #[cfg(feature = "time")]
+            Type::Duration(a) => Interval {
+                months: 0,
+                days: 0,
+                microseconds: i64::try_from(a.whole_microseconds())?,
+            }
+            .to_sql(ty, out),
+
             #[cfg(feature = "uuid")]
             Type::Uuid(a) => a.to_sql(ty, out),
         }
@@ -69,6 +122,190 @@ impl ToSql for Type<'_> {
     }
 }
 
+impl Type<'_> {
+    fn integer(&self) -> Option<i128> {
+        match *self {
+            Type::Int8(a) => Some(a.into()),
+            Type::Int16(a) => Some(a.into()),
+            Type::Int32(a) => Some(a.into()),
+            Type::Int64(a) => Some(a.into()),
+            Type::Isize(a) => i128::try_from(a).ok(),
+            Type::UInt8(a) => Some(a.into()),
+            Type::UInt16(a) => Some(a.into()),
+            Type::UInt32(a) => Some(a.into()),
+            Type::UInt64(a) => Some(a.into()),
+            Type::Usize(a) => i128::try_from(a).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the parameter types to prepare the statement with. Only the types
+/// of [Type::TypedNull] are fixed, `UNKNOWN` leaves the others to the server.
+fn param_types(args: &[Type]) -> Vec<tokio_postgres::types::Type> {
+    args.iter()
+        .map(|arg| match arg {
+            Type::TypedNull(ty) => ty.clone(),
+            _ => tokio_postgres::types::Type::UNKNOWN,
+        })
+        .collect()
+}
+
+/// Formats the time as a UTC `timestamptz` literal. The date is calculated
+/// from the days since the epoch, see
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+/// Postgres has no year zero and wants years before it suffixed with `BC`.
+fn timestamp_text(time: std::time::SystemTime) -> Result<String, crate::Error> {
+    let out_of_range = || crate::Error::InvalidArgument(format!("{time:?} is out of range"));
+    let micros = match time.duration_since(std::time::UNIX_EPOCH) {
+        Ok(since) => i64::try_from(since.as_micros()).map_err(|_| out_of_range())?,
+        Err(before) => -i64::try_from(before.duration().as_micros()).map_err(|_| out_of_range())?,
+    };
+
+    let days = micros.div_euclid(86_400_000_000);
+    let micros = micros.rem_euclid(86_400_000_000);
+
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let (year, era) = if year > 0 {
+        (year, "")
+    } else {
+        (1 - year, " BC")
+    };
+
+    Ok(format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}.{:06}+00{era}",
+        micros / 3_600_000_000,
+        micros / 60_000_000 % 60,
+        micros / 1_000_000 % 60,
+        micros % 1_000_000,
+    ))
+}
+
+/// Writes an integer in the binary `NUMERIC` format: the number of digits,
+/// the weight of the first digit, the sign and the display scale, followed by
+/// the digits in base 10000.
+fn numeric_to_sql(
+    value: i128,
+    out: &mut BytesMut,
+) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+    let mut digits = Vec::new();
+    let mut rest = value.unsigned_abs();
+
+    while rest > 0 {
+        digits.push((rest % 10000) as i16);
+        rest /= 10000;
+    }
+
+    let weight = digits.len().saturating_sub(1) as i16;
+    digits.reverse();
+
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+
+    let sign: u16 = if value < 0 { 0x4000 } else { 0 };
+
+    out.extend_from_slice(&(digits.len() as i16).to_be_bytes());
+    out.extend_from_slice(&weight.to_be_bytes());
+    out.extend_from_slice(&sign.to_be_bytes());
+    out.extend_from_slice(&0u16.to_be_bytes());
+
+    for digit in digits {
+        out.extend_from_slice(&digit.to_be_bytes());
+    }
+
+    Ok(IsNull::No)
+}
+
+/// Binary format: microseconds, days and months in network byte order.
+impl ToSql for Interval {
+    fn to_sql(
+        &self,
+        _: &tokio_postgres::types::Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        out.extend_from_slice(&self.microseconds.to_be_bytes());
+        out.extend_from_slice(&self.days.to_be_bytes());
+        out.extend_from_slice(&self.months.to_be_bytes());
+        Ok(IsNull::No)
+    }
+
+    accepts!(INTERVAL);
+    to_sql_checked!();
+}
+
+impl<'a> FromSql<'a> for Interval {
+    fn from_sql(
+        _: &tokio_postgres::types::Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let raw: [u8; 16] = raw.try_into()?;
+        let [m0, m1, m2, m3, m4, m5, m6, m7, d0, d1, d2, d3, n0, n1, n2, n3] = raw;
+
+        Ok(Interval {
+            months: i32::from_be_bytes([n0, n1, n2, n3]),
+            days: i32::from_be_bytes([d0, d1, d2, d3]),
+            microseconds: i64::from_be_bytes([m0, m1, m2, m3, m4, m5, m6, m7]),
+        })
+    }
+
+    accepts!(INTERVAL);
+}
+
+/// Positional conversion from a row, independent of the column names. It is
+/// implemented for tuples of up to eight [FromSqlOwned] values and can be
+/// implemented for own types by reading the columns by index:
+///
+/// ```
+/// use esql::PgFromRow;
+/// use tokio_postgres::Row;
+///
+/// struct User {
+///     id: i32,
+///     name: String,
+/// }
+///
+/// impl PgFromRow for User {
+///     fn from_row(row: &Row) -> Result<Self, tokio_postgres::Error> {
+///         Ok(User {
+///             id: row.try_get(0)?,
+///             name: row.try_get(1)?,
+///         })
+///     }
+/// }
+/// ```
+pub trait PgFromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self, tokio_postgres::Error>;
+}
+
+macro_rules! tuple_from_row {
+    ($($name:ident: $idx:tt),+) => {
+        impl<$($name: FromSqlOwned),+> PgFromRow for ($($name,)+) {
+            fn from_row(row: &Row) -> Result<Self, tokio_postgres::Error> {
+                Ok(($(row.try_get($idx)?,)+))
+            }
+        }
+    };
+}
+
+tuple_from_row!(A: 0);
+tuple_from_row!(A: 0, B: 1);
+tuple_from_row!(A: 0, B: 1, C: 2);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6);
+tuple_from_row!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7);
+
 fn slice_iter<'a>(s: &'a [Type<'a>]) -> impl ExactSizeIterator<Item = &'a dyn ToSql> + 'a {
     s.iter().map(|s| s as _)
 }
@@ -80,11 +317,29 @@ where
     fn get_raw(self, con: &C) -> impl Future<Output = Result<RowStream, crate::Error>>;
     fn execute(self, con: &C) -> impl Future<Output = Result<u64, crate::Error>>;
 
+    /// Returns the SQL of the query, which row errors refer to.
+    fn sql(&self) -> &str;
+
+    /// Runs the query with `COPY (...) TO STDOUT` and returns the raw output,
+    /// which is much faster than reading the rows one by one. `options` is
+    /// appended to the statement, e.g. `(FORMAT csv, HEADER)`; without it, the
+    /// rows are returned in text format, one line per row.
+    ///
+    /// As `COPY` does not support parameters, queries with bound arguments are
+    /// rejected with [crate::Error::InvalidArgument].
+    fn copy_out(
+        self,
+        con: &C,
+        options: impl Trusted,
+    ) -> impl Future<Output = Result<CopyOutStream, crate::Error>>;
+
     fn get<T>(self, con: &C) -> impl Future<Output = Result<Vec<T>, crate::Error>>
     where
         T: for<'de> Deserialize<'de>,
     {
         async move {
+            let sql = self.sql().to_owned();
+
             self.get_raw(con)
                 .await?
                 .collect::<Vec<_>>()
@@ -92,41 +347,125 @@ where
                 .into_iter()
                 .map(|row| {
                     if let Ok(r) = row {
-                        Self::deserialize_row(&r).map_err(|_| crate::Error::FromRowError)
+                        Self::deserialize_row_lenient(&r).map_err(|e| e.with_sql(&sql))
                     } else {
-                        Err(crate::Error::FromRowError)
+                        Err(crate::Error::from_row(&sql))
                     }
                 })
                 .collect()
         }
     }
 
+    /// Deserializes every row and collects them into a map, keyed by the value
+    /// of the given column. Returns [crate::Error::DuplicateKey] if two rows
+    /// share the same key.
+    fn get_map<K, V>(
+        self,
+        con: &C,
+        key_column: &str,
+    ) -> impl Future<Output = Result<HashMap<K, V>, crate::Error>>
+    where
+        K: FromSqlOwned + Eq + Hash,
+        V: for<'de> Deserialize<'de>,
+    {
+        async move {
+            let sql = self.sql().to_owned();
+            let mut map = HashMap::new();
+
+            for row in self.rows(con).await? {
+                let key = row
+                    .try_get(key_column)
+                    .map_err(|_| crate::Error::from_row(&sql))?;
+                let value = Self::deserialize_row_lenient(&row).map_err(|e| e.with_sql(&sql))?;
+
+                if map.insert(key, value).is_some() {
+                    return Err(crate::Error::DuplicateKey(key_column.to_string()));
+                }
+            }
+
+            Ok(map)
+        }
+    }
+
+    /// Converts every row by column position, see [PgFromRow].
+    fn get_positional<T>(self, con: &C) -> impl Future<Output = Result<Vec<T>, crate::Error>>
+    where
+        T: PgFromRow,
+    {
+        self.map_rows(con, T::from_row)
+    }
+
+    /// Collects the raw rows without deserializing them. Errors while reading
+    /// the stream are passed through.
+    fn rows(self, con: &C) -> impl Future<Output = Result<Vec<Row>, crate::Error>> {
+        async move {
+            self.get_raw(con)
+                .await?
+                .try_collect()
+                .await
+                .map_err(|e| e.into())
+        }
+    }
+
+    /// Applies the closure to every row. Errors returned by the closure, e.g.
+    /// from [Row::try_get], are passed through.
+    fn map_rows<R, E, F>(
+        self,
+        con: &C,
+        mut f: F,
+    ) -> impl Future<Output = Result<Vec<R>, crate::Error>>
+    where
+        F: FnMut(&Row) -> Result<R, E>,
+        E: Into<crate::Error>,
+    {
+        async move {
+            self.rows(con)
+                .await?
+                .iter()
+                .map(|row| f(row).map_err(|e| e.into()))
+                .collect()
+        }
+    }
+
     fn first<T>(self, con: &C) -> impl Future<Output = Result<Option<T>, crate::Error>>
     where
         T: for<'de> Deserialize<'de>,
     {
         async move {
+            let sql = self.sql().to_owned();
+
             match pin!(self.get_raw(con).await?).next().await {
                 None => Ok(None),
                 Some(row) => {
                     if let Ok(r) = row {
                         Ok(Some(
-                            Self::deserialize_row(&r).map_err(|_| crate::Error::FromRowError)?,
+                            Self::deserialize_row_lenient(&r).map_err(|e| e.with_sql(&sql))?,
                         ))
                     } else {
-                        Err(crate::Error::FromRowError)
+                        Err(crate::Error::from_row(&sql))
                     }
                 }
             }
         }
     }
 
+    /// Same as [PgQueryExt::first], but an empty result is reported as
+    /// [crate::Error::NotFound].
+    fn first_required<T>(self, con: &C) -> impl Future<Output = Result<T, crate::Error>>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        async move { self.first(con).await?.ok_or(crate::Error::NotFound) }
+    }
+
     fn pluck<T, I>(self, con: &C, idx: I) -> impl Future<Output = Result<Vec<T>, crate::Error>>
     where
         T: FromSqlOwned,
         I: RowIndex + ToOwned<Owned = I> + Display,
     {
         async move {
+            let sql = self.sql().to_owned();
+
             self.get_raw(con)
                 .await?
                 .collect::<Vec<_>>()
@@ -135,9 +474,9 @@ where
                 .map(|row| {
                     if let Ok(r) = row {
                         r.try_get(idx.to_owned())
-                            .map_err(|_| crate::Error::FromRowError)
+                            .map_err(|_| crate::Error::from_row(&sql))
                     } else {
-                        Err(crate::Error::FromRowError)
+                        Err(crate::Error::from_row(&sql))
                     }
                 })
                 .collect()
@@ -154,71 +493,300 @@ where
     fn value<T>(self, con: &C) -> impl Future<Output = Result<Option<T>, crate::Error>>
     where
         T: FromSqlOwned,
+    {
+        self.first_value(con, 0)
+    }
+
+    /// Reads the given column, by name or position, from the first row. Use
+    /// [PgQueryExt::pluck] to read it from every row.
+    fn first_value<T, I>(
+        self,
+        con: &C,
+        idx: I,
+    ) -> impl Future<Output = Result<Option<T>, crate::Error>>
+    where
+        T: FromSqlOwned,
+        I: RowIndex + Display,
     {
         async move {
+            let sql = self.sql().to_owned();
+
             match pin!(self.get_raw(con).await?).next().await {
                 None => Ok(None),
                 Some(row) => {
                     if let Ok(r) = row {
-                        Ok(Some(r.try_get(0).map_err(|_| crate::Error::FromRowError)?))
+                        Ok(Some(
+                            r.try_get(idx).map_err(|_| crate::Error::from_row(&sql))?,
+                        ))
                     } else {
-                        Err(crate::Error::FromRowError)
+                        Err(crate::Error::from_row(&sql))
                     }
                 }
             }
         }
     }
 
+    /// Reads the first column of a query that returns at most one row. Unlike
+    /// [PgQueryExt::value], which silently takes the first of many rows, this
+    /// fails with [crate::Error::UnexpectedRowCount] for more than one row.
+    fn scalar_opt<T>(self, con: &C) -> impl Future<Output = Result<Option<T>, crate::Error>>
+    where
+        T: FromSqlOwned,
+    {
+        async move {
+            let sql = self.sql().to_owned();
+            let mut rows = pin!(self.get_raw(con).await?);
+
+            let value = match rows.next().await {
+                None => return Ok(None),
+                Some(Ok(r)) => r.try_get(0).map_err(|_| crate::Error::from_row(&sql))?,
+                Some(Err(_)) => return Err(crate::Error::from_row(&sql)),
+            };
+
+            if rows.next().await.is_some() {
+                return Err(crate::Error::UnexpectedRowCount {
+                    expected: 1,
+                    got: 2 + rows.count().await,
+                    sql,
+                });
+            }
+
+            Ok(Some(value))
+        }
+    }
+
+    /// Same as [PgQueryExt::get], but gives up after the given duration with
+    /// [crate::Error::Timeout]. See [PgQueryExt::execute_timeout] for how the
+    /// running statement is cancelled.
+    fn get_timeout<T>(
+        self,
+        con: &C,
+        duration: Duration,
+    ) -> impl Future<Output = Result<Vec<T>, crate::Error>>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        async move {
+            let token = Self::cancel_token(con);
+
+            match tokio::time::timeout(duration, self.get(con)).await {
+                Ok(result) => result,
+                Err(_) => Err(cancel(token).await),
+            }
+        }
+    }
+
+    /// Same as [PgQueryExt::execute], but gives up after the given duration
+    /// with [crate::Error::Timeout]. For clients and transactions, a
+    /// cancellation request for the running statement is sent over a new,
+    /// unencrypted connection. Connections from a pool are not cancelled, so
+    /// the statement keeps running on the server.
+    fn execute_timeout(
+        self,
+        con: &C,
+        duration: Duration,
+    ) -> impl Future<Output = Result<u64, crate::Error>> {
+        async move {
+            let token = Self::cancel_token(con);
+
+            match tokio::time::timeout(duration, self.execute(con)).await {
+                Ok(result) => result,
+                Err(_) => Err(cancel(token).await),
+            }
+        }
+    }
+
+    /// Same as [PgQueryExt::execute], but retries the statement if it fails
+    /// with a serialization failure or a deadlock, which can happen under the
+    /// `REPEATABLE READ` and `SERIALIZABLE` isolation levels. The error of the
+    /// last attempt is returned once the retries are used up.
+    ///
+    /// Inside of a transaction, such errors abort the whole transaction, so
+    /// this is only useful for statements that run on their own.
+    fn execute_with_retry(
+        self,
+        con: &C,
+        policy: RetryPolicy,
+    ) -> impl Future<Output = Result<u64, crate::Error>>
+    where
+        Self: Clone,
+    {
+        async move {
+            let mut delay = policy.backoff;
+
+            for _ in 0..policy.max_retries {
+                match self.clone().execute(con).await {
+                    Err(crate::Error::PostgresError(e)) if is_retryable(&e) => {
+                        tokio::time::sleep(delay.min(policy.max_backoff)).await;
+                        delay = delay.saturating_mul(2);
+                    }
+                    result => return result,
+                }
+            }
+
+            self.execute(con).await
+        }
+    }
+
+    /// Returns the token used to cancel statements that timed out.
+    fn cancel_token(_con: &C) -> Option<CancelToken> {
+        None
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a column has a type the row deserializer cannot decode. Use
+    /// [PgQueryExt::deserialize_row_lenient] to get an error instead.
     fn deserialize_row<T: for<'de> Deserialize<'de>>(row: &Row) -> Result<T, Error> {
         Deserialize::deserialize(PgRow::from(row))
     }
+
+    /// Same as [PgQueryExt::deserialize_row], but returns an error for columns
+    /// of an unsupported type instead of panicking. This is used by [PgQueryExt::get]
+    /// and [PgQueryExt::first], so a single unexpected column only fails the
+    /// affected query with [crate::Error::UnsupportedType].
+    fn deserialize_row_lenient<T: for<'de> Deserialize<'de>>(row: &Row) -> Result<T, crate::Error> {
+        Deserialize::deserialize(PgRow::lenient(row)).map_err(crate::Error::from)
+    }
+}
+
+/// How often and how fast [PgQueryExt::execute_with_retry] retries.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// The delay before the first retry, which doubles with every further one.
+    pub backoff: Duration,
+    /// The upper limit for the doubled delay.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+fn is_retryable(error: &tokio_postgres::Error) -> bool {
+    error.code().is_some_and(|code| {
+        *code == SqlState::T_R_SERIALIZATION_FAILURE || *code == SqlState::T_R_DEADLOCK_DETECTED
+    })
+}
+
+/// Sends a cancellation request, if possible. Errors are ignored, as the
+/// statement has already been abandoned.
+async fn cancel(token: Option<CancelToken>) -> crate::Error {
+    if let Some(token) = token {
+        let _ = token.cancel_query(NoTls).await;
+    }
+
+    crate::Error::Timeout
 }
 
 impl<'a, S> PgQueryExt<'a, Client> for Query<'a, S> {
+    fn cancel_token(con: &Client) -> Option<CancelToken> {
+        Some(con.cancel_token())
+    }
+
+    fn sql(&self) -> &str {
+        Query::sql(self)
+    }
+
     fn get_raw(self, con: &Client) -> impl Future<Output = Result<RowStream, crate::Error>> {
         async move {
-            let (statement, args) = self.build(ArgFormat::Indexed);
+            let (statement, args) = self.build_default();
 
-            con.query_raw(&statement, slice_iter(&args))
-                .await
-                .map_err(|e| e.into())
+            observe(&statement, |_| None, async {
+                let prepared = con.prepare_typed(&statement, &param_types(&args)).await?;
+
+                con.query_raw(&prepared, slice_iter(&args))
+                    .await
+                    .map_err(|e| e.into())
+            })
+            .await
         }
     }
 
     fn execute(self, con: &Client) -> impl Future<Output = Result<u64, crate::Error>> {
         async move {
-            let (statement, args) = self.build(ArgFormat::Indexed);
+            let (statement, args) = self.build_default();
 
-            con.execute_raw(&statement, slice_iter(&args))
-                .await
-                .map_err(|e| e.into())
+            observe(&statement, |rows| Some(*rows), async {
+                let prepared = con.prepare_typed(&statement, &param_types(&args)).await?;
+
+                con.execute_raw(&prepared, slice_iter(&args))
+                    .await
+                    .map_err(|e| e.into())
+            })
+            .await
         }
     }
+
+    async fn copy_out(
+        self,
+        con: &Client,
+        options: impl Trusted,
+    ) -> Result<CopyOutStream, crate::Error> {
+        let statement = self.copy_out_statement(options)?;
+
+        Ok(con.copy_out(&statement).await?)
+    }
 }
 
 impl<'a, S> PgQueryExt<'a, Transaction<'a>> for Query<'a, S> {
+    fn cancel_token(con: &Transaction<'a>) -> Option<CancelToken> {
+        Some(con.cancel_token())
+    }
+
+    fn sql(&self) -> &str {
+        Query::sql(self)
+    }
+
     fn get_raw(
         self,
         con: &Transaction<'a>,
     ) -> impl Future<Output = Result<RowStream, crate::Error>> {
         async move {
-            let (statement, args) = self.build(ArgFormat::Indexed);
+            let (statement, args) = self.build_default();
 
-            con.query_raw(&statement, slice_iter(&args))
-                .await
-                .map_err(|e| e.into())
+            observe(&statement, |_| None, async {
+                let prepared = con.prepare_typed(&statement, &param_types(&args)).await?;
+
+                con.query_raw(&prepared, slice_iter(&args))
+                    .await
+                    .map_err(|e| e.into())
+            })
+            .await
         }
     }
 
     fn execute(self, con: &Transaction<'a>) -> impl Future<Output = Result<u64, crate::Error>> {
         async move {
-            let (statement, args) = self.build(ArgFormat::Indexed);
+            let (statement, args) = self.build_default();
 
-            con.execute_raw(&statement, slice_iter(&args))
-                .await
-                .map_err(|e| e.into())
+            observe(&statement, |rows| Some(*rows), async {
+                let prepared = con.prepare_typed(&statement, &param_types(&args)).await?;
+
+                con.execute_raw(&prepared, slice_iter(&args))
+                    .await
+                    .map_err(|e| e.into())
+            })
+            .await
         }
     }
+
+    async fn copy_out(
+        self,
+        con: &Transaction<'a>,
+        options: impl Trusted,
+    ) -> Result<CopyOutStream, crate::Error> {
+        let statement = self.copy_out_statement(options)?;
+
+        Ok(con.copy_out(&statement).await?)
+    }
 }
 
 #[cfg(feature = "qp-postgres")]
@@ -229,27 +797,395 @@ where
     T::TlsConnect: Send + Sync,
     <T::TlsConnect as TlsConnect<Socket>>::Future: Send,
 {
+    fn sql(&self) -> &str {
+        Query::sql(self)
+    }
+
     fn get_raw(self, con: &PgPool<T>) -> impl Future<Output = Result<RowStream, crate::Error>> {
         async move {
-            let (statement, args) = self.build(ArgFormat::Indexed);
+            let (statement, args) = self.build_default();
 
-            con.acquire()
-                .await?
-                .query_raw(&statement, slice_iter(&args))
-                .await
-                .map_err(|e| e.into())
+            observe(&statement, |_| None, async {
+                let con = con.acquire().await?;
+                let prepared = con.prepare_typed(&statement, &param_types(&args)).await?;
+
+                con.query_raw(&prepared, slice_iter(&args))
+                    .await
+                    .map_err(|e| e.into())
+            })
+            .await
         }
     }
 
     fn execute(self, con: &PgPool<T>) -> impl Future<Output = Result<u64, crate::Error>> {
         async move {
-            let (statement, args) = self.build(ArgFormat::Indexed);
+            let (statement, args) = self.build_default();
 
-            con.acquire()
-                .await?
-                .execute_raw(&statement, slice_iter(&args))
-                .await
-                .map_err(|e| e.into())
+            observe(&statement, |rows| Some(*rows), async {
+                let con = con.acquire().await?;
+                let prepared = con.prepare_typed(&statement, &param_types(&args)).await?;
+
+                con.execute_raw(&prepared, slice_iter(&args))
+                    .await
+                    .map_err(|e| e.into())
+            })
+            .await
+        }
+    }
+
+    async fn copy_out(
+        self,
+        con: &PgPool<T>,
+        options: impl Trusted,
+    ) -> Result<CopyOutStream, crate::Error> {
+        let statement = self.copy_out_statement(options)?;
+        let con = con.acquire().await?;
+
+        Ok(con.copy_out(&statement).await?)
+    }
+}
+
+impl<'a, S> Query<'a, S> {
+    /// Appends `RETURNING id` to an `INSERT` and returns the generated id of
+    /// the inserted row. The statement must insert exactly one row into a table
+    /// with an `id` column, otherwise [crate::Error::UnexpectedRowCount] is
+    /// returned.
+    pub async fn execute_returning_id<T, C>(self, con: &C) -> Result<T, crate::Error>
+    where
+        T: FromSqlOwned,
+        Query<'a, crate::query::Raw>: PgQueryExt<'a, C>,
+    {
+        let returning = crate::query::query(self) + "RETURNING id";
+        let sql = returning.sql().to_owned();
+
+        returning
+            .scalar_opt(con)
+            .await?
+            .ok_or(crate::Error::UnexpectedRowCount {
+                expected: 1,
+                got: 0,
+                sql,
+            })
+    }
+}
+
+impl<'a, S> Query<'a, S> {
+    /// Counts all rows the query returns without its pagination, see
+    /// [Query::without_limit], e.g. for the total of a paginated listing.
+    pub async fn count_over<C>(self, con: &C) -> Result<i64, crate::Error>
+    where
+        Query<'a, crate::query::Raw>: PgQueryExt<'a, C>,
+    {
+        let count = self
+            .without_limit()
+            .wrap("SELECT count(*) FROM (", ") AS t");
+        let sql = count.sql().to_owned();
+
+        count
+            .value(con)
+            .await?
+            .ok_or(crate::Error::UnexpectedRowCount {
+                expected: 1,
+                got: 0,
+                sql,
+            })
+    }
+}
+
+impl<S> Query<'_, S> {
+    /// Builds the `COPY (...) TO STDOUT` statement for [PgQueryExt::copy_out].
+    fn copy_out_statement(self, options: impl Trusted) -> Result<String, crate::Error> {
+        let (query, args) = self.build(ArgFormat::QuestionMark);
+
+        if !args.is_empty() {
+            return Err(crate::Error::InvalidArgument(
+                "COPY does not support bind parameters".into(),
+            ));
+        }
+
+        let statement = format!("COPY ({query}) TO STDOUT {}", options.to_string());
+
+        Ok(statement.trim_end().to_owned())
+    }
+}
+
+#[cfg(feature = "qp-postgres")]
+impl<S: Send + 'static> Query<'static, S> {
+    /// Runs the query on a connection of the pool and returns an owned stream
+    /// of the deserialized rows, which can be moved to another task. The
+    /// stream holds on to the connection until it is drained or dropped, so
+    /// no other query can use it while rows are still pending.
+    pub async fn into_stream_boxed<T, Tls>(
+        self,
+        pool: &PgPool<Tls>,
+    ) -> Result<futures_util::stream::BoxStream<'static, Result<T, crate::Error>>, crate::Error>
+    where
+        T: for<'de> Deserialize<'de> + Send + 'static,
+        Tls: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+        Tls::Stream: Send + Sync + 'static,
+        Tls::TlsConnect: Send + Sync,
+        <Tls::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    {
+        use futures_util::future::{select, Either};
+        use tokio::sync::{mpsc, oneshot};
+
+        let pool = pool.clone();
+        let sql = self.sql().to_owned();
+        let row_sql = sql.clone();
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let (row_tx, mut row_rx) = mpsc::channel(1);
+
+        // The connection guard borrows the pool, so it lives in this future,
+        // which forwards the rows and is polled as part of the stream.
+        let mut producer = Box::pin(async move {
+            let rows = async {
+                let con = pool.acquire().await?;
+                let rows = self.get_raw(&*con).await?;
+                Ok::<_, crate::Error>((con, rows))
+            }
+            .await;
+
+            let (_con, rows) = match rows {
+                Ok(rows) => {
+                    let _ = ready_tx.send(Ok(()));
+                    rows
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            let mut rows = pin!(rows);
+
+            while let Some(row) = rows.next().await {
+                let row = row.map_err(crate::Error::from).and_then(|row| {
+                    Deserialize::deserialize(PgRow::lenient(&row))
+                        .map_err(|e| crate::Error::from(e).with_sql(&row_sql))
+                });
+
+                if row_tx.send(row).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let ready = match select(producer.as_mut(), ready_rx).await {
+            Either::Left(((), ready_rx)) => ready_rx.await,
+            Either::Right((ready, _)) => ready,
+        };
+
+        ready.unwrap_or_else(|_| Err(crate::Error::from_row(&sql)))?;
+
+        let rows = futures_util::stream::poll_fn(move |cx| row_rx.poll_recv(cx));
+        let producer = futures_util::stream::once(producer).filter_map(|()| async { None });
+
+        Ok(futures_util::stream::select(rows, producer).boxed())
+    }
+}
+
+#[cfg(feature = "serde-json")]
+impl<'a, S> Query<'a, S> {
+    /// Prefixes the query with `EXPLAIN (FORMAT JSON)` and returns the plan
+    /// chosen by the planner. The statement itself is not executed.
+    pub async fn explain<C>(self, con: &C) -> Result<serde_json::Value, crate::Error>
+    where
+        Query<'a, crate::query::Raw>: PgQueryExt<'a, C>,
+    {
+        let explain = crate::query::query("EXPLAIN (FORMAT JSON)") + self;
+        let sql = explain.sql().to_owned();
+
+        explain
+            .value(con)
+            .await?
+            .ok_or(crate::Error::UnexpectedRowCount {
+                expected: 1,
+                got: 0,
+                sql,
+            })
+    }
+
+    /// Returns the total cost the planner estimated for the query, without
+    /// executing it. Statements that can't be explained, like DDL, fail with
+    /// the error reported by the server, a plan without a cost is reported as
+    /// [crate::Error::InvalidArgument].
+    pub async fn estimated_cost<C>(self, con: &C) -> Result<f64, crate::Error>
+    where
+        Query<'a, crate::query::Raw>: PgQueryExt<'a, C>,
+    {
+        self.explain(con)
+            .await?
+            .pointer("/0/Plan/Total Cost")
+            .and_then(serde_json::Value::as_f64)
+            .ok_or_else(|| crate::Error::InvalidArgument("query plan has no total cost".into()))
+    }
+
+    /// Same as [Query::explain], but uses `EXPLAIN ANALYZE`. Keep in mind that
+    /// this actually executes the statement, so wrap data-modifying queries in
+    /// a transaction that gets rolled back.
+    pub async fn explain_analyze<C>(self, con: &C) -> Result<serde_json::Value, crate::Error>
+    where
+        Query<'a, crate::query::Raw>: PgQueryExt<'a, C>,
+    {
+        let explain = crate::query::query("EXPLAIN (ANALYZE, FORMAT JSON)") + self;
+        let sql = explain.sql().to_owned();
+
+        explain
+            .value(con)
+            .await?
+            .ok_or(crate::Error::UnexpectedRowCount {
+                expected: 1,
+                got: 0,
+                sql,
+            })
+    }
+}
+
+/// Bulk-loads the rows into the table via `COPY ... FROM STDIN` in text
+/// format and returns the number of copied rows. Every row must contain one
+/// value per column, otherwise the copy is aborted with
+/// [crate::Error::RowArity] and nothing gets written.
+///
+/// Table and column names are interpolated into the statement, so they have to
+/// be [Trusted].
+pub async fn copy_in<'a, C, R>(
+    con: &C,
+    table: impl Trusted,
+    columns: impl IntoIterator<Item = impl Trusted>,
+    rows: impl IntoIterator<Item = R>,
+) -> Result<u64, crate::Error>
+where
+    C: GenericClient,
+    R: IntoIterator,
+    R::Item: Into<Type<'a>>,
+{
+    let columns: Vec<String> = columns.into_iter().map(|c| c.to_string()).collect();
+    let statement = format!(
+        "COPY {} ({}) FROM STDIN",
+        table.to_string(),
+        columns.join(", ")
+    );
+
+    let mut sink = pin!(con.client().copy_in(&statement).await?);
+    let mut buf = BytesMut::new();
+
+    for (i, row) in rows.into_iter().enumerate() {
+        let mut got = 0;
+
+        for arg in row {
+            if got > 0 {
+                buf.extend_from_slice(b"\t");
+            }
+
+            match copy_text(&arg.into())? {
+                Some(text) => copy_escape(&text, &mut buf),
+                None => buf.extend_from_slice(b"\\N"),
+            }
+
+            got += 1;
+        }
+
+        if got != columns.len() {
+            return Err(crate::Error::RowArity {
+                row: i,
+                expected: columns.len(),
+                got,
+            });
+        }
+
+        buf.extend_from_slice(b"\n");
+
+        if buf.len() >= COPY_CHUNK_SIZE {
+            sink.send(buf.split().freeze()).await?;
+        }
+    }
+
+    if !buf.is_empty() {
+        sink.send(buf.freeze()).await?;
+    }
+
+    Ok(sink.finish().await?)
+}
+
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Renders the value in the Postgres text representation. `None` stands for
+/// `NULL`.
+fn copy_text(arg: &Type) -> Result<Option<String>, crate::Error> {
+    Ok(Some(match arg {
+        Type::Null | Type::TypedNull(_) => return Ok(None),
+        Type::Secret(a) => return copy_text(a.expose()),
+        Type::Invalid(message) => return Err(crate::Error::InvalidArgument(message.clone())),
+        Type::Bool(a) => String::from(if *a { "t" } else { "f" }),
+        Type::Int8(a) => a.to_string(),
+        Type::Int16(a) => a.to_string(),
+        Type::Int32(a) => a.to_string(),
+        Type::Int64(a) => a.to_string(),
+        Type::Isize(a) => a.to_string(),
+        Type::UInt8(a) => a.to_string(),
+        Type::UInt16(a) => a.to_string(),
+        Type::UInt32(a) => a.to_string(),
+        Type::UInt64(a) => a.to_string(),
+        Type::Usize(a) => a.to_string(),
+        Type::Float(a) => a.to_string(),
+        Type::Double(a) => a.to_string(),
+        Type::String(a) => a.to_string(),
+        Type::Bytes(a) => a.iter().fold(String::from("\\x"), |mut hex, byte| {
+            hex.push_str(&format!("{byte:02x}"));
+            hex
+        }),
+        Type::SystemTime(a) => timestamp_text(*a)?,
+        Type::Array(a) => {
+            let elements = a
+                .iter()
+                .map(|arg| {
+                    Ok(match (arg, copy_text(arg)?) {
+                        (Type::Array(_), Some(text)) => text,
+                        (_, Some(text)) => {
+                            format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+                        }
+                        (_, None) => String::from("NULL"),
+                    })
+                })
+                .collect::<Result<Vec<_>, crate::Error>>()?;
+
+            format!("{{{}}}", elements.join(","))
+        }
+
+        #[cfg(feature = "decimal")]
+        Type::Decimal(a) => a.to_string(),
+
+        Type::Interval(a) => format!(
+            "{} mons {} days {} microseconds",
+            a.months, a.days, a.microseconds
+        ),
+
+        #[cfg(feature = "serde-json")]
+        Type::Json(a) => a.to_string(),
+
+        #[cfg(feature = "serde-json")]
+        Type::JsonRaw(a) => String::from_utf8(a.to_vec())
+            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
+
+        #[cfg(feature = "time")]
+        Type::OffsetDateTime(a) => a.to_string(),
+
+        #[cfg(feature = "time")]
+        Type::Duration(a) => format!("{} microseconds", a.whole_microseconds()),
+
+        #[cfg(feature = "uuid")]
+        Type::Uuid(a) => a.to_string(),
+    }))
+}
+
+fn copy_escape(text: &str, out: &mut BytesMut) {
+    for c in text.chars() {
+        match c {
+            '\\' => out.extend_from_slice(b"\\\\"),
+            '\n' => out.extend_from_slice(b"\\n"),
+            '\r' => out.extend_from_slice(b"\\r"),
+            '\t' => out.extend_from_slice(b"\\t"),
+            c => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
         }
     }
 }
@@ -1,22 +1,63 @@
 mod database;
+mod observer;
 mod query;
+#[cfg(feature = "tokio-postgres")]
 mod serde;
 mod types;
 
-pub use query::{expr, in_expr, query, trusted, ArgFormat, Expr, Query, TrustedString};
+pub use query::{
+    all, all_of, any, any_of, arg, coalesce, col, ddl, expr, fields, fields_aliased, filter, ident,
+    ident_mysql, in_expr, is_distinct_from, is_not_distinct_from, nullif, only, query, raw, select,
+    table, truncate, trusted, with_recursive, ArgFormat, Col, Expr, Fields, Query, QueryBuffer,
+    Select, Trusted, TrustedString,
+};
+
+pub use observer::{set_query_observer, QueryEvent, QueryObserver};
+
+pub use types::{secret, text, Secret, Type};
+
+#[cfg(feature = "tokio-postgres")]
+pub use types::{typed_null, Interval};
 
-pub use types::Type;
+#[cfg(feature = "serde-json")]
+pub use types::Json;
 
 // #[cfg(feature = "mysql-async")]
 // pub use database::mysql::MysqlQueryExt;
 
 #[cfg(feature = "tokio-postgres")]
-pub use database::pg::PgQueryExt;
+pub use database::pg::{copy_in, PgFromRow, PgQueryExt, RetryPolicy};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("query returned an unexpected number of rows")]
-    UnexpectedRowCount,
+    #[error("query `{sql}` returned {got} rows, expected {expected}")]
+    UnexpectedRowCount {
+        expected: usize,
+        got: usize,
+        sql: String,
+    },
+
+    #[error("query returned no rows")]
+    NotFound,
+
+    #[error("duplicate key in column {0}")]
+    DuplicateKey(String),
+
+    #[error("query has {expected} placeholders, but {got} arguments were given")]
+    ArgCountMismatch { expected: usize, got: usize },
+
+    #[error("invalid page {page} with {per_page} rows per page")]
+    InvalidPage { page: u64, per_page: u64 },
+
+    #[error("unbalanced parenthesis at byte {0} of the query")]
+    UnbalancedParentheses(usize),
+
+    #[error("row {row} has {got} values, expected {expected}")]
+    RowArity {
+        row: usize,
+        expected: usize,
+        got: usize,
+    },
 
     #[cfg(feature = "tokio-postgres")]
     #[error(transparent)]
@@ -26,6 +67,40 @@ pub enum Error {
     #[error(transparent)]
     MysqlError(#[from] mysql_async::Error),
 
-    #[error("conversion from a row failed")]
-    FromRowError,
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("query timed out")]
+    Timeout,
+
+    /// The statement is missing if the row was converted on its own, e.g. by
+    /// [PgQueryExt::deserialize_row_lenient].
+    #[error("conversion from a row{} failed", of_query(.sql))]
+    FromRowError { sql: Option<String> },
+
+    #[error("unsupported column type {0}")]
+    UnsupportedType(u32),
+}
+
+#[cfg(feature = "tokio-postgres")]
+impl Error {
+    pub(crate) fn from_row(sql: &str) -> Self {
+        Error::FromRowError {
+            sql: Some(sql.to_owned()),
+        }
+    }
+
+    /// Attaches the statement to a [Error::FromRowError] that has none yet.
+    pub(crate) fn with_sql(self, sql: &str) -> Self {
+        match self {
+            Error::FromRowError { sql: None } => Error::from_row(sql),
+            error => error,
+        }
+    }
+}
+
+fn of_query(sql: &Option<String>) -> String {
+    sql.as_ref()
+        .map(|sql| format!(" of `{sql}`"))
+        .unwrap_or_default()
 }
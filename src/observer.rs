@@ -0,0 +1,58 @@
+//! A global hook that gets notified about every executed query, e.g. to
+//! collect metrics.
+
+use std::{
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+/// Information about an executed query, passed to [QueryObserver::on_query].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct QueryEvent<'a> {
+    /// The statement as sent to the database, without arguments.
+    pub sql: &'a str,
+    /// Time until the database responded. For queries returning rows, this
+    /// does not include reading the rows.
+    pub duration: Duration,
+    /// Number of affected rows, if known. This is only the case for statements
+    /// that do not return rows.
+    pub rows: Option<u64>,
+    pub success: bool,
+}
+
+pub trait QueryObserver: Send + Sync {
+    fn on_query(&self, event: &QueryEvent<'_>);
+}
+
+static OBSERVER: OnceLock<Box<dyn QueryObserver>> = OnceLock::new();
+
+/// Registers the observer for all queries of the process. Only one observer
+/// can be registered, so this returns `false` if there already is one.
+pub fn set_query_observer(observer: impl QueryObserver + 'static) -> bool {
+    OBSERVER.set(Box::new(observer)).is_ok()
+}
+
+#[cfg_attr(not(feature = "tokio-postgres"), allow(dead_code))]
+pub(crate) async fn observe<T>(
+    sql: &str,
+    rows: impl FnOnce(&T) -> Option<u64>,
+    query: impl Future<Output = Result<T, crate::Error>>,
+) -> Result<T, crate::Error> {
+    let Some(observer) = OBSERVER.get() else {
+        return query.await;
+    };
+
+    let started = Instant::now();
+    let result = query.await;
+
+    observer.on_query(&QueryEvent {
+        sql,
+        duration: started.elapsed(),
+        rows: result.as_ref().ok().and_then(rows),
+        success: result.is_ok(),
+    });
+
+    result
+}
@@ -1,9 +1,21 @@
-use std::{fmt::Display, ops::Add};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::{Add, Div, Mul, Range, Sub},
+};
 
 use crate::Type;
 
-/// Marker trait for a trusted string-like value that can be used in a SQL query
-trait Trusted: ToString {}
+/// Marker trait for a trusted string-like value that can be used in a SQL query.
+/// It is sealed, so the only way to trust a runtime string is [trusted].
+pub trait Trusted: ToString + sealed::Sealed {}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for &'static str {}
+    impl Sealed for super::TrustedString {}
+}
 
 /// Wrapper type for a string whose trustworthiness must be validated by the
 /// developer. An instance of it can only be constructed by using the unsafe
@@ -44,18 +56,175 @@ pub unsafe fn trusted(value: impl ToString) -> TrustedString {
     TrustedString(value.to_string())
 }
 
-#[derive(Debug)]
+/// Quotes the identifier with double quotes, as used by Postgres and standard
+/// SQL. Embedded double quotes are escaped by doubling them, so the result is
+/// always a single identifier and can be trusted:
+///
+/// ```
+/// let q = esql::query("SELECT") + esql::ident("order") + "FROM items";
+///
+/// assert_eq!(q.to_string(), r#"SELECT "order" FROM items"#);
+/// ```
+pub fn ident(name: impl AsRef<str>) -> TrustedString {
+    quote_ident(name.as_ref(), '"')
+}
+
+/// Same as [ident], but quotes with backticks as used by MySQL.
+pub fn ident_mysql(name: impl AsRef<str>) -> TrustedString {
+    quote_ident(name.as_ref(), '`')
+}
+
+/// Renders a schema-qualified table name with both parts quoted like [ident]:
+///
+/// ```
+/// let q = esql::query("SELECT * FROM") + esql::table("audit", "events");
+///
+/// assert_eq!(q.to_string(), r#"SELECT * FROM "audit"."events""#);
+/// ```
+pub fn table(schema: impl AsRef<str>, name: impl AsRef<str>) -> TrustedString {
+    TrustedString(ident(schema).0 + "." + &ident(name).0)
+}
+
+/// Renders `ONLY` followed by the quoted table, which restricts a Postgres
+/// query to the table itself, excluding inheriting tables and partitions:
+///
+/// ```
+/// let q = esql::query("SELECT * FROM") + esql::only("measurements");
+///
+/// assert_eq!(q.to_string(), r#"SELECT * FROM ONLY "measurements""#);
+/// ```
+pub fn only(table: impl AsRef<str>) -> TrustedString {
+    TrustedString(String::from("ONLY ") + &ident(table).0)
+}
+
+fn quote_ident(name: &str, quote: char) -> TrustedString {
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push(quote);
+
+    for c in name.chars() {
+        if c == quote {
+            quoted.push(quote);
+        }
+
+        quoted.push(c);
+    }
+
+    quoted.push(quote);
+    TrustedString(quoted)
+}
+
+#[derive(Clone, Debug)]
 pub struct QueryBuffer<'a> {
     query: String,
     args: Vec<Type<'a>>,
 }
 
 impl<'a> QueryBuffer<'a> {
+    /// Appends one parenthesized group of placeholders per row, separated by
+    /// commas, and binds all values. Every row must have as many values as the
+    /// first one, otherwise [crate::Error::RowArity] is returned and the buffer
+    /// is left untouched.
+    ///
+    /// ```
+    /// use esql::QueryBuffer;
+    ///
+    /// let mut values = QueryBuffer::from("VALUES ");
+    /// values.append_iter([[1, 2], [3, 4]]).unwrap();
+    ///
+    /// let q = esql::query("INSERT INTO t (a, b)") + values;
+    ///
+    /// assert_eq!(q.to_string(), "INSERT INTO t (a, b) VALUES (?,?),(?,?)");
+    /// ```
+    pub fn append_iter<R>(&mut self, rows: impl IntoIterator<Item = R>) -> Result<(), crate::Error>
+    where
+        R: IntoIterator,
+        R::Item: Into<Type<'a>>,
+    {
+        let mut query = String::new();
+        let mut args = Vec::new();
+        let mut arity = None;
+
+        for (i, row) in rows.into_iter().enumerate() {
+            let start = args.len();
+            args.extend(row.into_iter().map(Into::into));
+            let len = args.len() - start;
+
+            match arity {
+                None => arity = Some(len),
+                Some(expected) if expected != len => {
+                    return Err(crate::Error::RowArity {
+                        row: i,
+                        expected,
+                        got: len,
+                    })
+                }
+                _ => {}
+            }
+
+            if i > 0 {
+                query.push(',');
+            }
+
+            query.push('(');
+            query.push_str("?,".repeat(len).trim_end_matches(','));
+            query.push(')');
+        }
+
+        self.query.push_str(&query);
+        self.args.append(&mut args);
+        Ok(())
+    }
+
+    /// Appends the fragment with the given separator instead of the single
+    /// space used by `+`:
+    ///
+    /// ```
+    /// use esql::QueryBuffer;
+    ///
+    /// let column = QueryBuffer::from("users").join_with(".", "email");
+    /// let q = esql::query("SELECT") + column + "FROM users";
+    ///
+    /// assert_eq!(q.to_string(), "SELECT users.email FROM users");
+    /// ```
+    pub fn join_with(
+        mut self,
+        separator: impl Trusted,
+        fragment: impl Into<QueryBuffer<'a>>,
+    ) -> Self {
+        self.push(&separator.to_string(), &mut fragment.into());
+        self
+    }
+
+    /// Inserts an argument at the given position, shifting all arguments after
+    /// it. This is needed when a placeholder is written before the fragment
+    /// that supplies the arguments preceding it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of arguments.
+    pub fn bind_at(mut self, index: usize, value: impl Into<Type<'a>>) -> Self {
+        self.args.insert(index, value.into());
+        self
+    }
+
     fn push(&mut self, glue: &str, other: &mut Self) {
         self.query.push_str(glue);
         self.query.push_str(&other.query);
         self.args.append(&mut other.args);
     }
+
+    fn join(glue: &str, items: impl IntoIterator<Item = impl Into<QueryBuffer<'a>>>) -> Self {
+        let mut buffer = QueryBuffer {
+            query: String::new(),
+            args: Vec::new(),
+        };
+
+        for (i, item) in items.into_iter().enumerate() {
+            buffer.push(if i == 0 { "" } else { glue }, &mut item.into());
+        }
+
+        buffer
+    }
 }
 
 impl<'a, T: Trusted> From<T> for QueryBuffer<'a> {
@@ -67,6 +236,17 @@ impl<'a, T: Trusted> From<T> for QueryBuffer<'a> {
     }
 }
 
+/// Binds a prebuilt list of arguments. The arguments are cloned, which is cheap
+/// for borrowed strings, but copies owned ones.
+impl<'a, T: Trusted> From<(T, &[Type<'a>])> for QueryBuffer<'a> {
+    fn from((query, args): (T, &[Type<'a>])) -> Self {
+        QueryBuffer {
+            query: query.to_string(),
+            args: args.to_vec(),
+        }
+    }
+}
+
 impl<'a, T, A1> Into<QueryBuffer<'a>> for (T, A1)
 where
     T: Trusted,
@@ -109,68 +289,600 @@ where
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Query<'a, S> {
     buffer: QueryBuffer<'a>,
     state: S,
+    /// Position of the `WHERE` predicate, only set in the [Where] state.
+    condition: Option<Condition>,
+    /// Position of the clause added by [Query::paginate] and the other
+    /// `LIMIT` methods, which [Query::without_limit] cuts off.
+    limit: Option<usize>,
+    format: ArgFormat,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+struct Condition {
+    /// Byte offset of the predicate.
+    start: usize,
+    /// Byte ranges of the conditions joined by `AND` since the last `OR`.
+    terms: Vec<Range<usize>>,
+}
+
+impl Condition {
+    fn shift(mut self, offset: usize) -> Self {
+        self.start += offset;
+
+        for term in &mut self.terms {
+            *term = term.start + offset..term.end + offset;
+        }
+
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Raw;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Where;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Having;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Suffixed;
 
+#[derive(Clone, Debug)]
+pub struct Locked;
+
+/// Marker trait for the states after which a row locking clause may follow.
+pub trait Lockable {}
+
+impl Lockable for Raw {}
+impl Lockable for Where {}
+impl Lockable for Suffixed {}
+
+/// Marker trait for the states after which `LIMIT` and `OFFSET` may follow.
+pub trait Limitable {}
+
+impl Limitable for Raw {}
+impl Limitable for Where {}
+impl Limitable for Having {}
+impl Limitable for Suffixed {}
+
 pub fn query<'a>(q: impl Into<QueryBuffer<'a>>) -> Query<'a, Raw> {
     Query {
         buffer: q.into(),
         state: Raw,
+        condition: None,
+        limit: None,
+        format: ArgFormat::default(),
+    }
+}
+
+/// Creates a statement without any arguments, like DDL that cannot be
+/// parameterized anyways. Only trusted strings are accepted, so dynamic parts
+/// have to pass through [trusted] explicitly:
+///
+/// ```compile_fail
+/// let table = String::from("users");
+/// let q = esql::ddl(format!("DROP TABLE {table}"));
+/// ```
+pub fn ddl<'a>(sql: impl Trusted) -> Query<'a, Suffixed> {
+    Query {
+        buffer: QueryBuffer::from(sql),
+        state: Suffixed,
+        condition: None,
+        limit: None,
+        format: ArgFormat::default(),
+    }
+}
+
+/// Creates a complete, hand-written statement with its arguments. Unlike
+/// [query], no clauses can be added, so it is ready to be executed:
+///
+/// ```
+/// let (sql, args) = esql::raw(("UPDATE users SET name = ? WHERE id = ?", "bob", 7))
+///     .build(esql::ArgFormat::Indexed);
+///
+/// assert_eq!(sql, "UPDATE users SET name = $1 WHERE id = $2");
+/// assert_eq!(args.len(), 2);
+/// ```
+pub fn raw<'a>(q: impl Into<QueryBuffer<'a>>) -> Query<'a, Suffixed> {
+    Query {
+        buffer: q.into(),
+        state: Suffixed,
+        condition: None,
+        limit: None,
+        format: ArgFormat::default(),
+    }
+}
+
+pub fn truncate<'a>(table: impl Trusted) -> Query<'a, Suffixed> {
+    let mut buffer = QueryBuffer::from("TRUNCATE TABLE");
+    buffer.push(" ", &mut QueryBuffer::from(table));
+
+    Query {
+        buffer,
+        state: Suffixed,
+        condition: None,
+        limit: None,
+        format: ArgFormat::default(),
+    }
+}
+
+/// Starts a query with a recursive common table expression. The arguments of
+/// the anchor are bound before those of the recursive part:
+///
+/// ```
+/// use esql::{query, with_recursive};
+///
+/// let q = with_recursive(
+///     "tree",
+///     ["id", "parent_id"],
+///     query("SELECT id, parent_id FROM nodes").wh("id = 1"),
+///     "SELECT n.id, n.parent_id FROM nodes n JOIN tree t ON n.parent_id = t.id",
+/// ) + "SELECT id FROM tree";
+///
+/// assert_eq!(
+///     q.to_string(),
+///     "WITH RECURSIVE tree(id, parent_id) AS (SELECT id, parent_id FROM nodes WHERE id = 1 \
+///      UNION ALL SELECT n.id, n.parent_id FROM nodes n JOIN tree t ON n.parent_id = t.id) \
+///      SELECT id FROM tree"
+/// );
+/// ```
+pub fn with_recursive<'a>(
+    name: impl Trusted,
+    columns: impl IntoIterator<Item = impl Trusted>,
+    anchor: impl Into<QueryBuffer<'a>>,
+    recursive: impl Into<QueryBuffer<'a>>,
+) -> Query<'a, Raw> {
+    let mut buffer = QueryBuffer::from("WITH RECURSIVE");
+    buffer.push(" ", &mut QueryBuffer::from(name));
+    buffer.push("(", &mut fields(columns).into());
+    buffer.push(") AS (", &mut anchor.into());
+    buffer.push(" UNION ALL ", &mut recursive.into());
+    buffer.query.push(')');
+
+    Query {
+        buffer,
+        state: Raw,
+        condition: None,
+        limit: None,
+        format: ArgFormat::default(),
     }
 }
 
 impl<'a> Query<'a, Raw> {
     pub fn wh(mut self, q: impl Into<QueryBuffer<'a>>) -> Query<'a, Where> {
+        let start = self.buffer.query.len() + " WHERE ".len();
         self.buffer.push(" WHERE ", &mut q.into());
 
+        let term = start..self.buffer.query.len();
+
         Query {
+            condition: Some(Condition {
+                start,
+                terms: vec![term],
+            }),
+            limit: None,
             buffer: self.buffer,
             state: Where,
+            format: self.format,
         }
     }
 
+    /// Starts the `WHERE` clause with the given expression, wrapped in
+    /// parentheses. Clone the [Expr] to reuse it in another query.
+    pub fn wh_expr(self, expr: Expr<'a>) -> Query<'a, Where> {
+        self.wh(expr)
+    }
+
+    /// Starts the `WHERE` clause with the conditions joined by `OR`, see
+    /// [any_of]. As the group is parenthesized, conditions added with
+    /// [Query::and] apply to all of them:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let q = query("SELECT * FROM users")
+    ///     .or_where(["role = 'admin'", "role = 'owner'"])
+    ///     .and(("active = ?", true));
+    ///
+    /// assert_eq!(
+    ///     q.to_string(),
+    ///     "SELECT * FROM users WHERE (role = 'admin' OR role = 'owner') AND active = ?"
+    /// );
+    /// ```
+    pub fn or_where(
+        self,
+        conditions: impl IntoIterator<Item = impl Into<QueryBuffer<'a>>>,
+    ) -> Query<'a, Where> {
+        self.wh(any_of(conditions))
+    }
+
+    /// Starts the `WHERE` clause with `1=1`, so every filter can be added with
+    /// [Query::and], regardless of whether it is the first one.
+    pub fn filtered(self) -> Query<'a, Where> {
+        self.wh("1=1")
+    }
+
+    /// Same as [Query::filtered], followed by all conditions that are not
+    /// `None`:
+    ///
+    /// ```
+    /// use esql::{expr, query};
+    ///
+    /// let name: Option<&str> = None;
+    /// let min_age = Some(18);
+    ///
+    /// let q = query("SELECT * FROM users").wh_all([
+    ///     name.map(|name| expr(("name = ?", name))),
+    ///     min_age.map(|age| expr(("age >= ?", age))),
+    /// ]);
+    ///
+    /// assert_eq!(q.to_string(), "SELECT * FROM users WHERE 1=1 AND (age >= ?)");
+    /// ```
+    pub fn wh_all<Q: Into<QueryBuffer<'a>>>(
+        self,
+        conditions: impl IntoIterator<Item = Option<Q>>,
+    ) -> Query<'a, Where> {
+        conditions
+            .into_iter()
+            .flatten()
+            .fold(self.filtered(), |q, condition| q.and(condition))
+    }
+
     pub fn having(mut self, q: impl Into<QueryBuffer<'a>>) -> Query<'a, Having> {
         self.buffer.push(" HAVING ", &mut q.into());
 
         Query {
             buffer: self.buffer,
             state: Having,
+            condition: None,
+            limit: None,
+            format: self.format,
+        }
+    }
+
+    /// Starts the `HAVING` clause with the given expression, wrapped in
+    /// parentheses.
+    pub fn having_expr(self, expr: Expr<'a>) -> Query<'a, Having> {
+        self.having(expr)
+    }
+
+    /// Appends `TABLESAMPLE method (?)` to sample the given percentage of the
+    /// table in the `FROM` clause, e.g. with `SYSTEM` or `BERNOULLI`:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let q = query("SELECT * FROM events").tablesample("SYSTEM", 10.0);
+    ///
+    /// assert_eq!(q.to_string(), "SELECT * FROM events TABLESAMPLE SYSTEM (?)");
+    /// ```
+    ///
+    /// The percentage is bound as a `real`, which is what Postgres expects.
+    pub fn tablesample(mut self, method: impl Trusted, percent: f32) -> Self {
+        let clause = format!(" TABLESAMPLE {} (?)", method.to_string());
+
+        self.buffer.query.push_str(&clause);
+        self.buffer.args.push(Type::Float(percent));
+        self
+    }
+}
+
+impl<'a> Query<'a, Suffixed> {
+    /// Reassembles a query from the parts returned by [Query::build]. This
+    /// allows caching the generated SQL and binding fresh arguments to it. As
+    /// the SQL is an owned string, it has to pass through [trusted] first.
+    pub fn from_parts(sql: TrustedString, args: Vec<Type<'a>>) -> Self {
+        Query {
+            buffer: QueryBuffer { query: sql.0, args },
+            state: Suffixed,
+            condition: None,
+            limit: None,
+            format: ArgFormat::default(),
+        }
+    }
+
+    /// Combines all queries with `UNION ALL`, like repeated calls to
+    /// [Query::union_all_query]. The arguments are bound in the order of the
+    /// queries. A single query is returned unchanged, and without any queries
+    /// the result is `SELECT WHERE false`, which returns no rows.
+    pub fn union_all_iter<S>(queries: impl IntoIterator<Item = Query<'a, S>>) -> Self {
+        let mut buffer = QueryBuffer::join(" UNION ALL ", queries);
+
+        if buffer.query.is_empty() {
+            buffer = QueryBuffer::from("SELECT WHERE false");
+        }
+
+        Query {
+            buffer,
+            state: Suffixed,
+            condition: None,
+            limit: None,
+            format: ArgFormat::default(),
+        }
+    }
+}
+
+impl<'a, S: Lockable> Query<'a, S> {
+    /// Appends `FOR UPDATE`, which locks the selected rows until the end of
+    /// the current transaction. Outside of a transaction, the lock is released
+    /// as soon as the statement finishes.
+    pub fn for_update(self) -> Query<'a, Locked> {
+        self.lock(" FOR UPDATE")
+    }
+
+    /// Appends `FOR SHARE`, see [Query::for_update].
+    pub fn for_share(self) -> Query<'a, Locked> {
+        self.lock(" FOR SHARE")
+    }
+
+    fn lock(mut self, clause: &str) -> Query<'a, Locked> {
+        self.buffer.query.push_str(clause);
+
+        Query {
+            buffer: self.buffer,
+            state: Locked,
+            condition: None,
+            limit: self.limit,
+            format: self.format,
+        }
+    }
+}
+
+impl<'a, S: Limitable> Query<'a, S> {
+    /// Appends `LIMIT ? OFFSET ?` for the given 1-indexed page. Returns
+    /// [crate::Error::InvalidPage] for page 0, an empty page or an offset that
+    /// does not fit into a `BIGINT`.
+    pub fn paginate(self, page: u64, per_page: u64) -> Result<Query<'a, Suffixed>, crate::Error> {
+        let invalid = || crate::Error::InvalidPage { page, per_page };
+
+        if page == 0 || per_page == 0 {
+            return Err(invalid());
+        }
+
+        let limit = i64::try_from(per_page).map_err(|_| invalid())?;
+        let offset = (page - 1)
+            .checked_mul(per_page)
+            .and_then(|offset| i64::try_from(offset).ok())
+            .ok_or_else(invalid)?;
+
+        Ok(self.suffix(("LIMIT ? OFFSET ?", limit, offset)))
+    }
+
+    /// Appends `LIMIT ? OFFSET ?` with the given number of rows to skip.
+    pub fn limit_offset(self, limit: i64, offset: i64) -> Query<'a, Suffixed> {
+        self.suffix(("LIMIT ? OFFSET ?", limit, offset))
+    }
+
+    /// Same as [Query::limit_offset], but in the `LIMIT offset, count` form
+    /// used by older MySQL setups. Note that the offset is bound first:
+    ///
+    /// ```
+    /// let (sql, args) = esql::query("SELECT * FROM users")
+    ///     .limit_offset_comma(10, 20)
+    ///     .build(esql::ArgFormat::QuestionMark);
+    ///
+    /// assert_eq!(sql, "SELECT * FROM users LIMIT ?, ?");
+    /// assert_eq!(args, [esql::Type::Int64(20), esql::Type::Int64(10)]);
+    /// ```
+    pub fn limit_offset_comma(self, limit: i64, offset: i64) -> Query<'a, Suffixed> {
+        self.suffix(("LIMIT ?, ?", offset, limit))
+    }
+
+    fn suffix(mut self, q: impl Into<QueryBuffer<'a>>) -> Query<'a, Suffixed> {
+        let limit = self.buffer.query.len();
+        self.buffer.push(" ", &mut q.into());
+
+        Query {
+            buffer: self.buffer,
+            state: Suffixed,
+            condition: None,
+            limit: Some(limit),
+            format: self.format,
+        }
+    }
+}
+
+impl<'a> Query<'a, Locked> {
+    /// Skips rows that are locked by another transaction instead of waiting.
+    pub fn skip_locked(self) -> Query<'a, Suffixed> {
+        self + "SKIP LOCKED"
+    }
+
+    /// Fails immediately if a row is locked by another transaction instead of
+    /// waiting.
+    pub fn nowait(self) -> Query<'a, Suffixed> {
+        self + "NOWAIT"
+    }
+}
+
+impl<'a, Q> Add<Q> for Query<'a, Locked>
+where
+    Q: Into<QueryBuffer<'a>>,
+{
+    type Output = Query<'a, Suffixed>;
+
+    fn add(mut self, rhs: Q) -> Self::Output {
+        self.buffer.push(" ", &mut rhs.into());
+
+        Query {
+            buffer: self.buffer,
+            state: Suffixed,
+            condition: None,
+            limit: self.limit,
+            format: self.format,
         }
     }
 }
 
 impl<'a> Query<'a, Where> {
     pub fn and(mut self, q: impl Into<QueryBuffer<'a>>) -> Query<'a, Where> {
+        let start = self.buffer.query.len() + " AND ".len();
         self.buffer.push(" AND ", &mut q.into());
+
+        if let Some(condition) = &mut self.condition {
+            condition.terms.push(start..self.buffer.query.len());
+        }
+
         self
     }
 
     pub fn or(mut self, q: impl Into<QueryBuffer<'a>>) -> Query<'a, Where> {
+        let start = self.buffer.query.len() + " OR ".len();
         self.buffer.push(" OR ", &mut q.into());
+
+        if let Some(condition) = &mut self.condition {
+            condition.terms.clear();
+            condition.terms.push(start..self.buffer.query.len());
+        }
+
         self
     }
 
+    /// Same as [Query::and], but skips the condition if the same SQL with the
+    /// same arguments is already part of the conditions joined by `AND`. As
+    /// `AND` binds stronger than `OR`, only the conditions after the last
+    /// [Query::or] are compared:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let q = query("SELECT * FROM users")
+    ///     .wh(("status = ?", 1))
+    ///     .and_unique(("status = ?", 1))
+    ///     .or("admin")
+    ///     .and_unique(("status = ?", 1));
+    ///
+    /// assert_eq!(
+    ///     q.to_string(),
+    ///     "SELECT * FROM users WHERE status = ? OR admin AND status = ?"
+    /// );
+    /// ```
+    pub fn and_unique(self, q: impl Into<QueryBuffer<'a>>) -> Query<'a, Where> {
+        let q = q.into();
+        let terms = self.condition.iter().flat_map(|c| &c.terms);
+
+        for term in terms {
+            let skipped = count_placeholders(&self.buffer.query[..term.start]);
+            let args = self.buffer.args.get(skipped..skipped + q.args.len());
+
+            if self.buffer.query[term.clone()] == q.query && args == Some(&q.args[..]) {
+                return self;
+            }
+        }
+
+        self.and(q)
+    }
+
+    /// Adds `column IN (subquery)`. The arguments of the subquery are bound
+    /// after the ones added so far:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let q = query("SELECT * FROM orders")
+    ///     .wh(("total > ?", 100))
+    ///     .and_in_subquery("user_id", query("SELECT id FROM users").wh("active"));
+    ///
+    /// assert_eq!(
+    ///     q.to_string(),
+    ///     "SELECT * FROM orders WHERE total > ? AND user_id IN (SELECT id FROM users WHERE active)"
+    /// );
+    /// ```
+    pub fn and_in_subquery(
+        self,
+        column: impl Into<QueryBuffer<'a>>,
+        subquery: impl Into<QueryBuffer<'a>>,
+    ) -> Query<'a, Where> {
+        self.and(in_subquery(" IN (", column, subquery))
+    }
+
+    /// Same as [Query::and_in_subquery], but with `NOT IN`. Keep in mind that
+    /// `NOT IN` matches nothing if the subquery returns a `NULL`.
+    pub fn and_not_in_subquery(
+        self,
+        column: impl Into<QueryBuffer<'a>>,
+        subquery: impl Into<QueryBuffer<'a>>,
+    ) -> Query<'a, Where> {
+        self.and(in_subquery(" NOT IN (", column, subquery))
+    }
+
+    /// Same as [Query::and] for `Some`, does nothing for `None`.
+    pub fn and_opt(self, q: Option<impl Into<QueryBuffer<'a>>>) -> Query<'a, Where> {
+        match q {
+            Some(q) => self.and(q),
+            None => self,
+        }
+    }
+
+    /// Same as [Query::or] for `Some`, does nothing for `None`.
+    pub fn or_opt(self, q: Option<impl Into<QueryBuffer<'a>>>) -> Query<'a, Where> {
+        match q {
+            Some(q) => self.or(q),
+            None => self,
+        }
+    }
+
+    /// Adds the conditions joined by `AND` as a parenthesized group with `OR`,
+    /// see [all_of]. As `AND` binds stronger than `OR`, this results in
+    /// `... WHERE a AND b OR (c AND d)`, which matches the rows that satisfy
+    /// either everything before or the whole new group.
+    pub fn or_group(
+        self,
+        conditions: impl IntoIterator<Item = impl Into<QueryBuffer<'a>>>,
+    ) -> Query<'a, Where> {
+        self.or(all_of(conditions))
+    }
+
     pub fn having(mut self, q: impl Into<QueryBuffer<'a>>) -> Query<'a, Having> {
         self.buffer.push(" HAVING ", &mut q.into());
 
         Query {
             buffer: self.buffer,
             state: Having,
+            condition: None,
+            limit: None,
+            format: self.format,
+        }
+    }
+
+    /// Starts the `HAVING` clause with the given expression, wrapped in
+    /// parentheses.
+    pub fn having_expr(self, expr: Expr<'a>) -> Query<'a, Having> {
+        self.having(expr)
+    }
+
+    /// Returns the predicate of the `WHERE` clause, without the keyword, and
+    /// the arguments bound within it. Everything added after [Query::wh] is
+    /// part of the predicate. This allows reusing a filter in another query:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let filter = query("SELECT * FROM users")
+    ///     .wh(("team_id = ?", 7))
+    ///     .and("active")
+    ///     .into_condition();
+    ///
+    /// let q = query(("UPDATE users SET seen = ?", true)).wh(filter);
+    ///
+    /// assert_eq!(q.to_string(), "UPDATE users SET seen = ? WHERE team_id = ? AND active");
+    /// ```
+    pub fn into_condition(mut self) -> QueryBuffer<'a> {
+        let start = self.condition.map_or(0, |c| c.start);
+        let skipped = count_placeholders(&self.buffer.query[..start]);
+
+        QueryBuffer {
+            query: self.buffer.query.split_off(start),
+            args: self
+                .buffer
+                .args
+                .split_off(skipped.min(self.buffer.args.len())),
         }
     }
 }
@@ -199,6 +911,9 @@ where
         Query {
             buffer: self.buffer,
             state: Suffixed,
+            condition: None,
+            limit: None,
+            format: self.format,
         }
     }
 }
@@ -215,6 +930,9 @@ where
         Query {
             buffer: self.buffer,
             state: Suffixed,
+            condition: None,
+            limit: None,
+            format: self.format,
         }
     }
 }
@@ -231,29 +949,165 @@ where
     }
 }
 
-pub struct Expr<'a>(QueryBuffer<'a>);
+impl<'a, S> From<Query<'a, S>> for QueryBuffer<'a> {
+    fn from(value: Query<'a, S>) -> Self {
+        value.buffer
+    }
+}
+
+/// A parenthesized group of conditions. Whenever the connective changes, the
+/// conditions so far are wrapped in parentheses, so the expression is read
+/// from left to right:
+///
+/// ```
+/// use esql::expr;
+///
+/// let q = esql::query("SELECT * FROM t").wh(expr("a").or("b").and("c"));
+///
+/// assert_eq!(q.to_string(), "SELECT * FROM t WHERE ((a OR b) AND c)");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Expr<'a> {
+    buffer: QueryBuffer<'a>,
+    connective: Option<&'static str>,
+}
 
 pub fn expr<'a>(q: impl Into<QueryBuffer<'a>>) -> Expr<'a> {
-    Expr(q.into())
+    Expr {
+        buffer: q.into(),
+        connective: None,
+    }
 }
 
 impl<'a> Expr<'a> {
-    pub fn and(mut self, q: impl Into<QueryBuffer<'a>>) -> Self {
-        self.0.push(" AND ", &mut q.into());
+    pub fn and(self, q: impl Into<QueryBuffer<'a>>) -> Self {
+        self.push(" AND ", q)
+    }
+
+    pub fn or(self, q: impl Into<QueryBuffer<'a>>) -> Self {
+        self.push(" OR ", q)
+    }
+
+    /// Same as [Expr::and] for `Some`, does nothing for `None`.
+    pub fn and_opt(self, q: Option<impl Into<QueryBuffer<'a>>>) -> Self {
+        match q {
+            Some(q) => self.and(q),
+            None => self,
+        }
+    }
+
+    /// Same as [Expr::or] for `Some`, does nothing for `None`.
+    pub fn or_opt(self, q: Option<impl Into<QueryBuffer<'a>>>) -> Self {
+        match q {
+            Some(q) => self.or(q),
+            None => self,
+        }
+    }
+
+    /// Same as `.and(in_expr(subject, values))`, see [in_expr]. An empty list
+    /// of values still renders `1=0`:
+    ///
+    /// ```
+    /// use esql::expr;
+    ///
+    /// let e = expr("active").and_in("role", ["admin", "owner"]);
+    /// let q = esql::query("SELECT * FROM users").wh(e);
+    ///
+    /// assert_eq!(q.to_string(), "SELECT * FROM users WHERE (active AND role IN (?,?))");
+    /// ```
+    pub fn and_in(
+        self,
+        subject: impl Into<QueryBuffer<'a>>,
+        values: impl IntoIterator<Item = impl Into<Type<'a>>>,
+    ) -> Self {
+        self.and(in_expr(subject, values))
+    }
+
+    /// Same as `.or(in_expr(subject, values))`, see [Expr::and_in].
+    pub fn or_in(
+        self,
+        subject: impl Into<QueryBuffer<'a>>,
+        values: impl IntoIterator<Item = impl Into<Type<'a>>>,
+    ) -> Self {
+        self.or(in_expr(subject, values))
+    }
+
+    /// Adds `column IN (subquery)` with `AND`, see [Query::and_in_subquery].
+    pub fn and_in_subquery(
+        self,
+        column: impl Into<QueryBuffer<'a>>,
+        subquery: impl Into<QueryBuffer<'a>>,
+    ) -> Self {
+        self.and(in_subquery(" IN (", column, subquery))
+    }
+
+    /// Same as [Expr::and_in_subquery], but with `NOT IN`.
+    pub fn and_not_in_subquery(
+        self,
+        column: impl Into<QueryBuffer<'a>>,
+        subquery: impl Into<QueryBuffer<'a>>,
+    ) -> Self {
+        self.and(in_subquery(" NOT IN (", column, subquery))
+    }
+
+    /// Exclusive or. This is only supported by MySQL.
+    pub fn xor(self, q: impl Into<QueryBuffer<'a>>) -> Self {
+        self.push(" XOR ", q)
+    }
+
+    /// Wraps the conditions so far in parentheses.
+    pub fn group(mut self) -> Self {
+        self.buffer.query = String::from("(") + &self.buffer.query + ")";
+        self.connective = None;
         self
     }
 
-    pub fn or(mut self, q: impl Into<QueryBuffer<'a>>) -> Self {
-        self.0.push(" OR ", &mut q.into());
+    fn push(mut self, connective: &'static str, q: impl Into<QueryBuffer<'a>>) -> Self {
+        if self.connective.is_some_and(|c| c != connective) {
+            self = self.group();
+        }
+
+        self.buffer.push(connective, &mut q.into());
+        self.connective = Some(connective);
         self
     }
 }
 
 impl<'a> From<Expr<'a>> for QueryBuffer<'a> {
-    fn from(mut value: Expr<'a>) -> Self {
-        value.0.query = String::from("(") + &value.0.query + ")";
-        value.0
+    fn from(value: Expr<'a>) -> Self {
+        value.group().buffer
+    }
+}
+
+/// Joins the conditions with `AND` and wraps them in parentheses. Without any
+/// conditions, this evaluates to `1=1`.
+pub fn all_of<'a>(
+    conditions: impl IntoIterator<Item = impl Into<QueryBuffer<'a>>>,
+) -> QueryBuffer<'a> {
+    group(" AND ", "1=1", conditions)
+}
+
+/// Joins the conditions with `OR` and wraps them in parentheses. Without any
+/// conditions, this evaluates to `1=0`.
+pub fn any_of<'a>(
+    conditions: impl IntoIterator<Item = impl Into<QueryBuffer<'a>>>,
+) -> QueryBuffer<'a> {
+    group(" OR ", "1=0", conditions)
+}
+
+fn group<'a>(
+    glue: &str,
+    empty: &'static str,
+    conditions: impl IntoIterator<Item = impl Into<QueryBuffer<'a>>>,
+) -> QueryBuffer<'a> {
+    let mut buffer = QueryBuffer::join(glue, conditions);
+
+    if buffer.query.is_empty() {
+        return QueryBuffer::from(empty);
     }
+
+    buffer.query = String::from("(") + &buffer.query + ")";
+    buffer
 }
 
 pub fn in_expr<'a>(
@@ -276,45 +1130,708 @@ pub fn in_expr<'a>(
     buffer
 }
 
+fn in_subquery<'a>(
+    operator: &str,
+    column: impl Into<QueryBuffer<'a>>,
+    subquery: impl Into<QueryBuffer<'a>>,
+) -> QueryBuffer<'a> {
+    let mut buffer = column.into();
+    buffer.push(operator, &mut subquery.into());
+    buffer.query.push(')');
+    buffer
+}
+
+/// Renders `subject = ANY(?)` and binds the values as a single array
+/// parameter. Unlike [in_expr], the SQL does not depend on the number of
+/// values. An empty array matches no rows.
+pub fn any<'a>(
+    subject: impl Into<QueryBuffer<'a>>,
+    values: impl IntoIterator<Item = impl Into<Type<'a>>>,
+) -> QueryBuffer<'a> {
+    array_comparison(subject, "ANY", values)
+}
+
+/// Renders `subject = ALL(?)`, see [any]. An empty array matches every row.
+pub fn all<'a>(
+    subject: impl Into<QueryBuffer<'a>>,
+    values: impl IntoIterator<Item = impl Into<Type<'a>>>,
+) -> QueryBuffer<'a> {
+    array_comparison(subject, "ALL", values)
+}
+
+fn array_comparison<'a>(
+    subject: impl Into<QueryBuffer<'a>>,
+    quantifier: &str,
+    values: impl IntoIterator<Item = impl Into<Type<'a>>>,
+) -> QueryBuffer<'a> {
+    let mut buffer = subject.into();
+    let values = Type::Array(values.into_iter().map(Into::into).collect());
+
+    buffer.query.push_str(" = ");
+    buffer.query.push_str(quantifier);
+    buffer.query.push('(');
+    buffer.push("", &mut arg(values));
+    buffer.query.push(')');
+    buffer
+}
+
+/// Null-safe inequality: `subject IS DISTINCT FROM ?`. Unlike `<>`, this is
+/// true when exactly one side is `NULL` and false when both are.
+pub fn is_distinct_from<'a>(
+    subject: impl Into<QueryBuffer<'a>>,
+    value: impl Into<Type<'a>>,
+) -> QueryBuffer<'a> {
+    let mut buffer = subject.into();
+    buffer.push(" IS DISTINCT FROM ", &mut arg(value));
+    buffer
+}
+
+/// Null-safe equality: `subject IS NOT DISTINCT FROM ?`. Unlike `=`, this is
+/// true when both sides are `NULL`.
+pub fn is_not_distinct_from<'a>(
+    subject: impl Into<QueryBuffer<'a>>,
+    value: impl Into<Type<'a>>,
+) -> QueryBuffer<'a> {
+    let mut buffer = subject.into();
+    buffer.push(" IS NOT DISTINCT FROM ", &mut arg(value));
+    buffer
+}
+
+/// Turns a single value into a `?` placeholder with the value bound to it. This
+/// allows mixing columns and values in helpers like [coalesce]:
+///
+/// ```
+/// use esql::{arg, coalesce, query, QueryBuffer};
+///
+/// let q = query("SELECT")
+///     + coalesce([QueryBuffer::from("nickname"), arg("anonymous")])
+///     + "FROM users";
+///
+/// assert_eq!(q.to_string(), "SELECT COALESCE(nickname, ?) FROM users");
+/// ```
+pub fn arg<'a>(value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+    QueryBuffer {
+        query: String::from("?"),
+        args: vec![value.into()],
+    }
+}
+
+pub fn coalesce<'a>(
+    items: impl IntoIterator<Item = impl Into<QueryBuffer<'a>>>,
+) -> QueryBuffer<'a> {
+    let mut buffer = QueryBuffer::from("COALESCE(");
+    buffer.push("", &mut QueryBuffer::join(", ", items));
+    buffer.query.push(')');
+    buffer
+}
+
+pub fn nullif<'a>(a: impl Into<QueryBuffer<'a>>, b: impl Into<QueryBuffer<'a>>) -> QueryBuffer<'a> {
+    let mut buffer = QueryBuffer::from("NULLIF(");
+    buffer.push("", &mut a.into());
+    buffer.push(", ", &mut b.into());
+    buffer.query.push(')');
+    buffer
+}
+
+/// Renders `aggregate FILTER (WHERE condition)`, which restricts the rows a
+/// Postgres aggregate is computed over. The arguments of the condition are
+/// bound after those of the aggregate:
+///
+/// ```
+/// use esql::{filter, query};
+///
+/// let q = query("SELECT") + filter("count(*)", ("status = ?", "open")) + "FROM tickets";
+///
+/// assert_eq!(q.to_string(), "SELECT count(*) FILTER (WHERE status = ?) FROM tickets");
+/// ```
+pub fn filter<'a>(
+    aggregate: impl Into<QueryBuffer<'a>>,
+    condition: impl Into<QueryBuffer<'a>>,
+) -> QueryBuffer<'a> {
+    let mut buffer = aggregate.into();
+    buffer.push(" FILTER (WHERE ", &mut condition.into());
+    buffer.query.push(')');
+    buffer
+}
+
+/// Operand for building arithmetic and comparison expressions without writing
+/// the placeholders by hand. Columns go through the trusted path, whereas the
+/// operands are bound as arguments in the order they are applied:
+///
+/// ```
+/// use esql::{col, query};
+///
+/// let q = query("SELECT * FROM products").wh((col("price") * 1.19).gt(100.0));
+///
+/// assert_eq!(q.to_string(), "SELECT * FROM products WHERE price * ? > ?");
+/// ```
+pub struct Col<'a> {
+    buffer: QueryBuffer<'a>,
+    compound: bool,
+}
+
+pub fn col<'a>(name: impl Into<QueryBuffer<'a>>) -> Col<'a> {
+    Col {
+        buffer: name.into(),
+        compound: false,
+    }
+}
+
+impl<'a> Col<'a> {
+    fn arithmetic(mut self, op: &str, value: impl Into<Type<'a>>) -> Self {
+        // Chained operations are evaluated from left to right, regardless of
+        // the operator precedence in SQL.
+        if self.compound {
+            self.buffer.query = String::from("(") + &self.buffer.query + ")";
+        }
+
+        self.buffer.push(op, &mut arg(value));
+        self.compound = true;
+        self
+    }
+
+    fn compare(mut self, op: &str, value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+        self.buffer.push(op, &mut arg(value));
+        self.buffer
+    }
+
+    pub fn eq(self, value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+        self.compare(" = ", value)
+    }
+
+    pub fn ne(self, value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+        self.compare(" <> ", value)
+    }
+
+    pub fn gt(self, value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+        self.compare(" > ", value)
+    }
+
+    pub fn ge(self, value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+        self.compare(" >= ", value)
+    }
+
+    pub fn lt(self, value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+        self.compare(" < ", value)
+    }
+
+    pub fn le(self, value: impl Into<Type<'a>>) -> QueryBuffer<'a> {
+        self.compare(" <= ", value)
+    }
+}
+
+impl<'a, V: Into<Type<'a>>> Add<V> for Col<'a> {
+    type Output = Self;
+
+    fn add(self, rhs: V) -> Self::Output {
+        self.arithmetic(" + ", rhs)
+    }
+}
+
+impl<'a, V: Into<Type<'a>>> Sub<V> for Col<'a> {
+    type Output = Self;
+
+    fn sub(self, rhs: V) -> Self::Output {
+        self.arithmetic(" - ", rhs)
+    }
+}
+
+impl<'a, V: Into<Type<'a>>> Mul<V> for Col<'a> {
+    type Output = Self;
+
+    fn mul(self, rhs: V) -> Self::Output {
+        self.arithmetic(" * ", rhs)
+    }
+}
+
+impl<'a, V: Into<Type<'a>>> Div<V> for Col<'a> {
+    type Output = Self;
+
+    fn div(self, rhs: V) -> Self::Output {
+        self.arithmetic(" / ", rhs)
+    }
+}
+
+impl<'a> From<Col<'a>> for QueryBuffer<'a> {
+    fn from(value: Col<'a>) -> Self {
+        value.buffer
+    }
+}
+
 pub struct Fields<'a>(QueryBuffer<'a>);
 
-pub fn fields<'a>(items: impl IntoIterator<Item = impl Into<Type<'a>>>) -> Fields<'a> {
-    todo!()
+/// Renders a comma-separated list of trusted columns, e.g. for a projection.
+pub fn fields<'a>(items: impl IntoIterator<Item = impl Trusted>) -> Fields<'a> {
+    Fields(QueryBuffer::join(", ", items))
+}
+
+/// Renders a comma-separated list of `column AS alias` pairs:
+///
+/// ```
+/// use esql::{fields_aliased, query};
+///
+/// let q = query("SELECT") + fields_aliased([("users.id", "id"), ("countries.name", "country")]);
+///
+/// assert_eq!(q.to_string(), "SELECT users.id AS id, countries.name AS country");
+/// ```
+pub fn fields_aliased<'a>(
+    items: impl IntoIterator<Item = (impl Trusted, impl Trusted)>,
+) -> Fields<'a> {
+    Fields(QueryBuffer::join(
+        ", ",
+        items.into_iter().map(|(field, alias)| {
+            let mut buffer = QueryBuffer::from(field);
+            buffer.push(" AS ", &mut QueryBuffer::from(alias));
+            buffer
+        }),
+    ))
+}
+
+impl<'a> From<Fields<'a>> for QueryBuffer<'a> {
+    fn from(value: Fields<'a>) -> Self {
+        value.0
+    }
+}
+
+impl<'a, T: Trusted, const N: usize> From<[T; N]> for Fields<'a> {
+    fn from(value: [T; N]) -> Self {
+        fields(value)
+    }
 }
 
+/// The projection of a `SELECT`, which becomes a [Query] once the table is
+/// given with [Select::from].
+pub struct Select<'a>(QueryBuffer<'a>);
+
+/// Starts a `SELECT` with the given columns, either as an array of trusted
+/// names or as [fields] or [fields_aliased]:
+///
+/// ```
+/// use esql::select;
+///
+/// let q = select(["id", "name"]).from("users").wh(("active = ?", true));
+///
+/// assert_eq!(q.to_string(), "SELECT id, name FROM users WHERE active = ?");
+/// ```
+pub fn select<'a>(projection: impl Into<Fields<'a>>) -> Select<'a> {
+    let mut buffer = QueryBuffer::from("SELECT");
+    buffer.push(" ", &mut projection.into().into());
+    Select(buffer)
+}
+
+impl<'a> Select<'a> {
+    pub fn from(self, table: impl Trusted) -> Query<'a, Raw> {
+        query(self.0) + "FROM" + table
+    }
+
+    /// Same as [Select::from], but excludes inheriting tables, see [only].
+    pub fn from_only(self, table: impl AsRef<str>) -> Query<'a, Raw> {
+        query(self.0) + "FROM" + only(table)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ArgFormat {
     QuestionMark,
+    #[default]
     Indexed,
+    /// Like [ArgFormat::Indexed], but equal arguments share a single `$N`
+    /// placeholder. Keep in mind that Postgres infers one type per parameter,
+    /// so the same value must not be used in differently typed contexts.
+    IndexedDedup,
+    /// `@p1`, `@p2`, ... as used by SQL Server drivers.
+    AtIndexed,
 }
 
 impl<'a, T> Query<'a, T> {
-    pub fn build(self, format: ArgFormat) -> (String, Vec<Type<'a>>) {
-        if let ArgFormat::Indexed = format {
-            self.build_indexed()
-        } else {
-            (self.buffer.query, self.buffer.args)
+    /// Returns the query with `?` placeholders, like the [Display] implementation.
+    pub fn sql(&self) -> &str {
+        &self.buffer.query
+    }
+
+    /// Same as [Query::sql], for use as a cache key, e.g. for prepared
+    /// statements. Queries that only differ in their arguments share the same
+    /// key, which identifies the built statement for [ArgFormat::QuestionMark]
+    /// and [ArgFormat::Indexed]. It doesn't for [ArgFormat::IndexedDedup], as
+    /// the placeholders there depend on which arguments are equal.
+    pub fn sql_key(&self) -> &str {
+        self.sql()
+    }
+
+    pub fn sql_len(&self) -> usize {
+        self.buffer.query.len()
+    }
+
+    pub fn arg_count(&self) -> usize {
+        self.buffer.args.len()
+    }
+
+    /// Returns the bound arguments in placeholder order, without consuming
+    /// the query like [Query::build] does.
+    pub fn args(&self) -> &[Type<'a>] {
+        &self.buffer.args
+    }
+
+    /// Combines both queries with `UNION`. The arguments of `other` are bound
+    /// after those of `self`, so [Query::build] numbers the placeholders
+    /// across the whole statement.
+    pub fn union_query<S>(self, other: Query<'a, S>) -> Query<'a, Suffixed> {
+        self.set_operation(" UNION ", other)
+    }
+
+    /// Same as [Query::union_query], but uses `UNION ALL`.
+    pub fn union_all_query<S>(self, other: Query<'a, S>) -> Query<'a, Suffixed> {
+        self.set_operation(" UNION ALL ", other)
+    }
+
+    fn set_operation<S>(mut self, operator: &str, other: Query<'a, S>) -> Query<'a, Suffixed> {
+        self.buffer.push(operator, &mut other.into());
+
+        Query {
+            buffer: self.buffer,
+            state: Suffixed,
+            condition: None,
+            limit: None,
+            format: self.format,
         }
     }
 
-    fn build_indexed(self) -> (String, Vec<Type<'a>>) {
-        let mut n = 0;
+    /// Replaces the bound arguments, keeping the SQL and the state. This allows
+    /// building a query once and executing it with different values. Returns
+    /// [crate::Error::ArgCountMismatch] if the number of arguments does not
+    /// match the number of placeholders.
+    pub fn rebind(mut self, args: Vec<Type<'a>>) -> Result<Self, crate::Error> {
+        let expected = count_placeholders(&self.buffer.query);
+
+        if args.len() != expected {
+            return Err(crate::Error::ArgCountMismatch {
+                expected,
+                got: args.len(),
+            });
+        }
+
+        self.buffer.args = args;
+        Ok(self)
+    }
 
-        (
-            // TODO: Enhance this process and support question marks in strings
+    /// Same as [QueryBuffer::bind_at].
+    pub fn bind_at(mut self, index: usize, value: impl Into<Type<'a>>) -> Self {
+        self.buffer = self.buffer.bind_at(index, value);
+        self
+    }
+
+    /// Puts the fragment in front of the query, separated by a space. Its
+    /// arguments are bound before the existing ones.
+    pub fn prepend(mut self, fragment: impl Into<QueryBuffer<'a>>) -> Self {
+        let mut buffer = fragment.into();
+        let shift = buffer.query.len() + 1;
+        buffer.push(" ", &mut self.buffer);
+        self.buffer = buffer;
+        self.condition = self.condition.map(|c| c.shift(shift));
+        self.limit = self.limit.map(|limit| limit + shift);
+        self
+    }
+
+    /// Removes the clause added by [Query::paginate], [Query::limit_offset] or
+    /// [Query::limit_offset_comma] together with its arguments, e.g. to count
+    /// all matching rows. Anything appended after it is removed as well, and
+    /// a `LIMIT` added as plain SQL is kept:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let q = query("SELECT * FROM users")
+    ///     .wh(("active = ?", true))
+    ///     .paginate(3, 20)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(q.without_limit().to_string(), "SELECT * FROM users WHERE active = ?");
+    /// ```
+    pub fn without_limit(mut self) -> Self {
+        if let Some(limit) = self.limit.take() {
+            self.buffer.query.truncate(limit);
             self.buffer
-                .query
-                .chars()
-                .map(|c| match c {
-                    '?' => {
-                        n = n + 1;
-                        String::from("$") + &n.to_string()
-                    }
-                    c => c.to_string(),
-                })
-                .collect::<Vec<_>>()
-                .concat(),
-            self.buffer.args,
-        )
+                .args
+                .truncate(count_placeholders(&self.buffer.query));
+        }
+
+        self
+    }
+
+    /// Appends the fragment without a separator.
+    pub fn append_tight(mut self, fragment: impl Into<QueryBuffer<'a>>) -> Self {
+        self.buffer.push("", &mut fragment.into());
+        self
+    }
+
+    /// Turns the query into a subquery of a new one, which starts with
+    /// `prefix` and continues with `suffix` right after the subquery:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let q = query("SELECT * FROM users")
+    ///     .wh(("active = ?", true))
+    ///     .wrap("SELECT count(*) FROM (", ") AS sub");
+    ///
+    /// assert_eq!(
+    ///     q.to_string(),
+    ///     "SELECT count(*) FROM (SELECT * FROM users WHERE active = ?) AS sub"
+    /// );
+    /// ```
+    pub fn wrap(
+        mut self,
+        prefix: impl Into<QueryBuffer<'a>>,
+        suffix: impl Into<QueryBuffer<'a>>,
+    ) -> Query<'a, Raw> {
+        let mut buffer = prefix.into();
+        buffer.push("", &mut self.buffer);
+        buffer.push("", &mut suffix.into());
+
+        Query {
+            buffer,
+            state: Raw,
+            condition: None,
+            limit: None,
+            format: self.format,
+        }
+    }
+
+    /// Renders the query as a derived table `(...) AS alias`, keeping its
+    /// arguments, so it can be used as a source in the `FROM` clause of
+    /// another query:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let active = query("SELECT * FROM users").wh(("active = ?", true));
+    /// let q = query("SELECT count(*) FROM") + active.as_subquery("sub");
+    ///
+    /// assert_eq!(
+    ///     q.to_string(),
+    ///     "SELECT count(*) FROM (SELECT * FROM users WHERE active = ?) AS sub"
+    /// );
+    /// ```
+    pub fn as_subquery(mut self, alias: impl Trusted) -> QueryBuffer<'a> {
+        let mut buffer = QueryBuffer::from("(");
+        buffer.push("", &mut self.buffer);
+        buffer.push(") AS ", &mut alias.into());
+        buffer
+    }
+
+    /// Appends the fragment, separated by a space, only if `condition` is true.
+    /// Unlike `+`, this keeps the current state, so it can be used in the
+    /// middle of a chain:
+    ///
+    /// ```
+    /// use esql::query;
+    ///
+    /// let only_active = false;
+    ///
+    /// let q = query("SELECT * FROM users")
+    ///     .append_if(only_active, "JOIN active_users USING (id)")
+    ///     .wh(("id > ?", 10));
+    ///
+    /// assert_eq!(q.to_string(), "SELECT * FROM users WHERE id > ?");
+    /// ```
+    pub fn append_if(mut self, condition: bool, fragment: impl Into<QueryBuffer<'a>>) -> Self {
+        if condition {
+            self.buffer.push(" ", &mut fragment.into());
+        }
+
+        self
+    }
+
+    /// Appends a `/* ... */` comment, e.g. for tagging queries in
+    /// `pg_stat_statements`. Nested comment delimiters in the text are broken
+    /// up, so it cannot terminate the comment early. Placeholders inside the
+    /// comment are left untouched by [Query::build].
+    pub fn comment(mut self, text: impl AsRef<str>) -> Self {
+        let text = text.as_ref().replace("*/", "* /").replace("/*", "/ *");
+        self.buffer.query.push_str(" /* ");
+        self.buffer.query.push_str(&text);
+        self.buffer.query.push_str(" */");
+        self
+    }
+
+    /// Sets the placeholder format used by [Query::build_default] and the
+    /// database backends. Defaults to [ArgFormat::Indexed].
+    pub fn arg_format(mut self, format: ArgFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Same as [Query::build] with the format set via [Query::arg_format].
+    pub fn build_default(self) -> (String, Vec<Type<'a>>) {
+        let format = self.format;
+        self.build(format)
+    }
+
+    pub fn build(self, format: ArgFormat) -> (String, Vec<Type<'a>>) {
+        let mut out = String::with_capacity(self.buffer.query.len());
+        let args = render(&self.buffer.query, self.buffer.args, format, &mut out);
+
+        (out, args)
+    }
+
+    /// Checks that the parentheses of the query are balanced, ignoring those
+    /// in string literals, quoted identifiers and comments. Returns
+    /// [crate::Error::UnbalancedParentheses] with the byte offset of the first
+    /// closing parenthesis without a match, or else of the first one that is
+    /// never closed.
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        let mut open = Vec::new();
+        let mut quote = None;
+        let mut comment = false;
+        let mut chars = self.buffer.query.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match (quote, comment, c) {
+                (None, false, '/') if chars.next_if(|&(_, c)| c == '*').is_some() => comment = true,
+                (_, true, '*') if chars.next_if(|&(_, c)| c == '/').is_some() => comment = false,
+                (None, false, '\'' | '"') => quote = Some(c),
+                (Some(q), _, c) if q == c => quote = None,
+                (None, false, '(') => open.push(i),
+                (None, false, ')') if open.pop().is_none() => {
+                    return Err(crate::Error::UnbalancedParentheses(i))
+                }
+                _ => {}
+            }
+        }
+
+        match open.first() {
+            Some(&i) => Err(crate::Error::UnbalancedParentheses(i)),
+            None => Ok(()),
+        }
+    }
+
+    /// Builds the query for drivers that take named parameters. The
+    /// placeholders become `:p0`, `:p1`, ... and the arguments are returned in
+    /// a map keyed by those names, without the colon.
+    pub fn build_named(self) -> (String, HashMap<String, Type<'a>>) {
+        let mut out = String::with_capacity(self.buffer.query.len());
+
+        write_placeholders(&self.buffer.query, &mut out, |n, out| {
+            out.push_str(":p");
+            out.push_str(&n.to_string());
+        });
+
+        let args = self
+            .buffer
+            .args
+            .into_iter()
+            .enumerate()
+            .map(|(n, arg)| (format!("p{n}"), arg))
+            .collect();
+
+        (out, args)
+    }
+
+    /// Same as [Query::build], but writes the SQL into `out`, which is cleared
+    /// first. This allows reusing one allocation for many queries. As the
+    /// query is only borrowed, the arguments are cloned.
+    pub fn build_to(&self, out: &mut String, format: ArgFormat) -> Vec<Type<'a>> {
+        out.clear();
+        render(&self.buffer.query, self.buffer.args.clone(), format, out)
+    }
+}
+
+fn render<'a>(
+    query: &str,
+    args: Vec<Type<'a>>,
+    format: ArgFormat,
+    out: &mut String,
+) -> Vec<Type<'a>> {
+    match format {
+        ArgFormat::QuestionMark => {
+            write_placeholders(query, out, |_, out| out.push('?'));
+            args
+        }
+        ArgFormat::Indexed => {
+            write_placeholders(query, out, |n, out| {
+                out.push('$');
+                out.push_str(&(n + 1).to_string());
+            });
+            args
+        }
+        ArgFormat::IndexedDedup => render_indexed_dedup(query, args, out),
+        ArgFormat::AtIndexed => {
+            write_placeholders(query, out, |n, out| {
+                out.push_str("@p");
+                out.push_str(&(n + 1).to_string());
+            });
+            args
+        }
+    }
+}
+
+fn render_indexed_dedup<'a>(query: &str, args: Vec<Type<'a>>, out: &mut String) -> Vec<Type<'a>> {
+    let mut deduped: Vec<Type<'a>> = Vec::new();
+    let mut source = args.into_iter();
+
+    write_placeholders(query, out, |_, out| {
+        let index = match source.next() {
+            Some(arg) => match deduped.iter().position(|a| a == &arg) {
+                Some(index) => index,
+                None => {
+                    deduped.push(arg);
+                    deduped.len() - 1
+                }
+            },
+            None => deduped.len(),
+        };
+
+        out.push('$');
+        out.push_str(&(index + 1).to_string());
+    });
+
+    deduped.extend(source);
+    deduped
+}
+
+fn count_placeholders(query: &str) -> usize {
+    let mut count = 0;
+    write_placeholders(query, &mut String::new(), |_, _| count += 1);
+    count
+}
+
+/// Writes the query while passing every `?` placeholder to the given closure.
+/// Question marks inside of string literals or quoted identifiers are kept,
+/// and `??` is written as a literal `?`, e.g. for the JSON operator.
+fn write_placeholders(
+    query: &str,
+    out: &mut String,
+    mut placeholder: impl FnMut(usize, &mut String),
+) {
+    let mut quote = None;
+    let mut comment = false;
+    let mut prev = None;
+    let mut n = 0;
+    let mut chars = query.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, comment, prev, c) {
+            (None, false, Some('/'), '*') => comment = true,
+            (_, true, Some('*'), '/') => comment = false,
+            (None, false, _, '\'' | '"') => quote = Some(c),
+            (Some(q), _, _, c) if q == c => quote = None,
+            (None, false, _, '?') if chars.next_if_eq(&'?').is_some() => {}
+            (None, false, _, '?') => {
+                placeholder(n, out);
+                n += 1;
+                prev = Some(c);
+                continue;
+            }
+            _ => {}
+        }
+
+        // A closing `*/` must not reuse the `*` of the opening `/*`
+        prev = if comment && c == '*' && prev == Some('/') {
+            None
+        } else {
+            Some(c)
+        };
+        out.push(c);
     }
 }
 
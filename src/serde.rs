@@ -5,7 +5,7 @@ use std::{
 };
 
 use serde::{
-    de::{MapAccess, Visitor},
+    de::{value::SeqDeserializer, MapAccess, SeqAccess, Visitor},
     Deserializer,
 };
 use time::OffsetDateTime;
@@ -17,11 +17,15 @@ use tokio_postgres::{
 #[derive(Debug)]
 pub enum Error {
     Unknown,
+    UnsupportedType(u32),
 }
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("unknown")
+        match self {
+            Error::Unknown => formatter.write_str("unknown"),
+            Error::UnsupportedType(oid) => write!(formatter, "unsupported column type {oid}"),
+        }
     }
 }
 
@@ -33,10 +37,31 @@ impl serde::de::Error for Error {
 
 impl std::error::Error for Error {}
 
+impl From<Error> for crate::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Unknown => crate::Error::FromRowError { sql: None },
+            Error::UnsupportedType(oid) => crate::Error::UnsupportedType(oid),
+        }
+    }
+}
+
 pub struct PgRow<'a> {
     columns: slice::Iter<'a, Column>,
     values: slice::Iter<'a, Column>,
     row: &'a Row,
+    lenient: bool,
+}
+
+impl<'a> PgRow<'a> {
+    /// Creates a row deserializer that returns [Error::UnsupportedType] for
+    /// columns it cannot decode, instead of panicking.
+    pub fn lenient(row: &'a Row) -> Self {
+        Self {
+            lenient: true,
+            ..Self::from(row)
+        }
+    }
 }
 
 impl<'a> From<&'a Row> for PgRow<'a> {
@@ -45,6 +70,7 @@ impl<'a> From<&'a Row> for PgRow<'a> {
             columns: row.columns().iter(),
             values: row.columns().iter(),
             row,
+            lenient: false,
         }
     }
 }
@@ -66,9 +92,45 @@ impl<'a, 'de> MapAccess<'de> for PgRow<'a> {
     where
         V: serde::de::DeserializeSeed<'de>,
     {
-        let key = self.values.next();
+        // The value belongs to the column returned by the last key, so it is
+        // read by position. Looking it up by name would be linear per field.
+        let index = self.row.len() - self.values.len();
+        self.values.next();
+
+        let col: Option<PgCol> = self.row.try_get(index).unwrap();
 
-        seed.deserialize(PgOptCol(self.row.try_get(key.unwrap().name()).unwrap()))
+        seed.deserialize(PgOptCol(col.map(|col| PgCol {
+            lenient: self.lenient,
+            ..col
+        })))
+    }
+}
+
+/// Yields the columns by position, for tuples and tuple structs.
+impl<'a, 'de> SeqAccess<'de> for PgRow<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let index = self.row.len() - self.values.len();
+
+        if self.values.next().is_none() {
+            return Ok(None);
+        }
+
+        let col: Option<PgCol> = self.row.try_get(index).unwrap();
+
+        seed.deserialize(PgOptCol(col.map(|col| PgCol {
+            lenient: self.lenient,
+            ..col
+        })))
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
     }
 }
 
@@ -123,16 +185,27 @@ impl<'a, 'de> Deserializer<'de> for PgOptCol<'a> {
         }
     }
 
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(col) => col.deserialize_tuple(len, visitor),
+            None => visitor.visit_none(),
+        }
+    }
+
     ::serde::forward_to_deserialize_any! {
         bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit
         seq bytes byte_buf map unit_struct newtype_struct
-        tuple_struct struct tuple enum identifier ignored_any
+        tuple_struct struct enum identifier ignored_any
     }
 }
 
 pub struct PgCol<'a> {
     ty: tokio_postgres::types::Type,
     raw: &'a [u8],
+    lenient: bool,
 }
 
 impl<'a> FromSql<'a> for PgCol<'a> {
@@ -143,6 +216,7 @@ impl<'a> FromSql<'a> for PgCol<'a> {
         Ok(Self {
             ty: ty.to_owned(),
             raw,
+            lenient: false,
         })
     }
 
@@ -211,20 +285,52 @@ impl<'a, 'de> Deserializer<'de> for PgCol<'a> {
             Type::BOOL => visitor.visit_bool(FromSql::from_sql(&self.ty, &self.raw).unwrap()),
             Type::FLOAT4 => visitor.visit_f32(FromSql::from_sql(&self.ty, &self.raw).unwrap()),
             Type::FLOAT8 => visitor.visit_f64(FromSql::from_sql(&self.ty, &self.raw).unwrap()),
+            // The single-byte "char" type of the catalogs, not char(n)
+            Type::CHAR => {
+                visitor.visit_char(i8::from_sql(&self.ty, self.raw).unwrap() as u8 as char)
+            }
             Type::INT2 => visitor.visit_i16(FromSql::from_sql(&self.ty, &self.raw).unwrap()),
             Type::INT4 => visitor.visit_i32(FromSql::from_sql(&self.ty, &self.raw).unwrap()),
             Type::INT8 => visitor.visit_i64(FromSql::from_sql(&self.ty, &self.raw).unwrap()),
-            Type::TEXT | Type::VARCHAR | Type::BPCHAR => {
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::XML => {
                 visitor.visit_string(FromSql::from_sql(&self.ty, &self.raw).unwrap())
             }
 
+            // citext is an extension type without a fixed OID
+            ref ty if ty.name() == "citext" => {
+                visitor.visit_string(FromSql::from_sql(&self.ty, self.raw).unwrap())
+            }
+
             Type::INET => {
                 visitor.visit_string(IpAddr::from_sql(&self.ty, &self.raw).unwrap().to_string())
             }
 
+            Type::INTERVAL => {
+                let interval =
+                    crate::Interval::from_sql(&self.ty, self.raw).map_err(|_| Error::Unknown)?;
+
+                visitor.visit_seq(SeqDeserializer::new(
+                    [
+                        i64::from(interval.months),
+                        i64::from(interval.days),
+                        interval.microseconds,
+                    ]
+                    .into_iter(),
+                ))
+            }
+
+            // Decoded by hand, as rust_decimal panics on NaN and on values
+            // beyond its range. A Decimal field then fails to parse the text.
+            Type::NUMERIC => visitor.visit_string(numeric_text(self.raw).ok_or(Error::Unknown)?),
+
             #[cfg(feature = "serde-json")]
             Type::JSONB | Type::JSON => {
-                visitor.visit_bytes(FromSql::from_sql(&self.ty, &self.raw).unwrap())
+                use serde::de::Error as _;
+
+                serde_json::Value::from_sql(&self.ty, self.raw)
+                    .unwrap()
+                    .deserialize_any(visitor)
+                    .map_err(Error::custom)
             }
 
             #[cfg(feature = "uuid")]
@@ -238,6 +344,7 @@ impl<'a, 'de> Deserializer<'de> for PgCol<'a> {
                     .unwrap(),
             ),
 
+            ty if self.lenient => Err(Error::UnsupportedType(ty.oid())),
             ty => unimplemented!("{:?}", ty),
         }
     }
@@ -247,15 +354,45 @@ impl<'a, 'de> Deserializer<'de> for PgCol<'a> {
         V: Visitor<'de>,
     {
         if let Some(col) = <Option<Self> as FromSql>::from_sql(&self.ty, &self.raw).unwrap() {
-            visitor.visit_some(col)
+            visitor.visit_some(PgCol {
+                lenient: self.lenient,
+                ..col
+            })
         } else {
             visitor.visit_none()
         }
     }
 
+    /// Durations like `time::Duration` are read from an interval as a pair of
+    /// seconds and nanoseconds, counting a day as 24 hours. Months have no
+    /// fixed length, so intervals with months can't be read this way.
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.ty != Type::INTERVAL || len != 2 {
+            return self.deserialize_any(visitor);
+        }
+
+        let interval = crate::Interval::from_sql(&self.ty, self.raw).map_err(|_| Error::Unknown)?;
+
+        if interval.months != 0 {
+            return Err(Error::Unknown);
+        }
+
+        let microseconds = i64::from(interval.days)
+            .checked_mul(86_400_000_000)
+            .and_then(|days| days.checked_add(interval.microseconds))
+            .ok_or(Error::Unknown)?;
+
+        visitor.visit_seq(SeqDeserializer::new(
+            [microseconds / 1_000_000, microseconds % 1_000_000 * 1000].into_iter(),
+        ))
+    }
+
     ::serde::forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        bytes byte_buf unit unit_struct newtype_struct seq
         tuple_struct map struct enum identifier ignored_any
     }
 }
@@ -267,7 +404,7 @@ impl<'a, 'de> Deserializer<'de> for PgRow<'a> {
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.deserialize_map(visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -289,9 +426,86 @@ impl<'a, 'de> Deserializer<'de> for PgRow<'a> {
         self.deserialize_map(visitor)
     }
 
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(self)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
     ::serde::forward_to_deserialize_any! {
         bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit option
-        seq bytes byte_buf unit_struct newtype_struct
-        tuple_struct tuple enum identifier ignored_any
+        bytes byte_buf unit_struct newtype_struct enum identifier ignored_any
     }
 }
+
+/// Renders a NUMERIC in the binary format as Postgres would print it: the
+/// number of digits, the weight of the first digit, the sign and the display
+/// scale, followed by the digits in base 10000.
+fn numeric_text(raw: &[u8]) -> Option<String> {
+    let word = |i: usize| Some(u16::from_be_bytes([*raw.get(2 * i)?, *raw.get(2 * i + 1)?]));
+
+    let ndigits = usize::from(word(0)?);
+    let weight = i64::from(word(1)? as i16);
+    let scale = usize::from(word(3)?);
+    let digit = |i: i64| match usize::try_from(i) {
+        Ok(i) if i < ndigits => word(4 + i),
+        _ => Some(0),
+    };
+
+    let mut text = match word(2)? {
+        0x0000 => String::new(),
+        0x4000 => String::from("-"),
+        0xC000 => return Some(String::from("NaN")),
+        0xD000 => return Some(String::from("Infinity")),
+        0xF000 => return Some(String::from("-Infinity")),
+        _ => return None,
+    };
+
+    if weight < 0 {
+        text.push('0');
+    }
+
+    for i in 0..=weight {
+        let group = digit(i)?;
+
+        if i == 0 {
+            text.push_str(&group.to_string());
+        } else {
+            text.push_str(&format!("{group:04}"));
+        }
+    }
+
+    if scale > 0 {
+        let mut fraction = String::new();
+
+        for i in 0..scale.div_ceil(4) as i64 {
+            fraction.push_str(&format!("{:04}", digit(weight + 1 + i)?));
+        }
+
+        fraction.truncate(scale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+
+    Some(text)
+}
@@ -5,7 +5,7 @@ macro_rules! make_args {
 		simple {$($target:ident($source:ty),)+}
 		extra {$($extra:tt)*}
 	) => {
-		#[derive(Debug, PartialEq)]
+		#[derive(Clone, Debug, PartialEq)]
         pub enum Type<'a> {
 			$($target($source),)+
 			$($extra)*
@@ -15,6 +15,12 @@ macro_rules! make_args {
             fn into(self) -> Type<'a> {
                 Type::$target(self)
             }
+        })+
+
+		$(impl<'a> From<&$source> for Type<'a> {
+            fn from(value: &$source) -> Self {
+                Type::$target(*value)
+            }
         })+
     };
 }
@@ -38,18 +44,182 @@ make_args! {
     extra {
         Null,
         String(Cow<'a, str>),
+        Bytes(Cow<'a, [u8]>),
+        SystemTime(std::time::SystemTime),
+        Array(Vec<Type<'a>>),
+        Secret(Secret<'a>),
+
+        /// A value that could not be converted, e.g. a [Json] value that
+        /// failed to serialize. Executing a query with it fails with the
+        /// contained message.
+        Invalid(String),
+
+        #[cfg(feature = "decimal")]
+        Decimal(rust_decimal::Decimal),
+
+        #[cfg(feature = "tokio-postgres")]
+        Interval(Interval),
+
+        #[cfg(feature = "tokio-postgres")]
+        TypedNull(tokio_postgres::types::Type),
 
         #[cfg(feature = "serde-json")]
         Json(serde_json::Value),
 
+        /// Already serialized JSON, bound to `json` and `jsonb` parameters
+        /// as is. The bytes are not parsed, so making sure they are valid
+        /// JSON is up to the caller. The server rejects malformed input.
+        #[cfg(feature = "serde-json")]
+        JsonRaw(Cow<'a, [u8]>),
+
         #[cfg(feature = "time")]
         OffsetDateTime(time::OffsetDateTime),
 
+        #[cfg(feature = "time")]
+        Duration(time::Duration),
+
         #[cfg(feature = "uuid")]
         Uuid(uuid::Uuid),
     }
 }
 
+/// A value that is bound like the wrapped one, but is printed as `***` by
+/// [Debug], so it does not end up in logs. Created by [secret].
+#[derive(Clone, PartialEq)]
+pub struct Secret<'a>(Box<Type<'a>>);
+
+impl<'a> Secret<'a> {
+    pub fn expose(&self) -> &Type<'a> {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("***")
+    }
+}
+
+/// Marks the value as sensitive, see [Secret]:
+///
+/// ```
+/// use esql::{query, secret};
+///
+/// let q = query("SELECT * FROM users").wh(("password_hash = ?", secret("s3cr3t")));
+///
+/// assert!(!format!("{q:?}").contains("s3cr3t"));
+/// ```
+pub fn secret<'a>(value: impl Into<Type<'a>>) -> Type<'a> {
+    Type::Secret(Secret(Box::new(value.into())))
+}
+
+/// Binds anything that can be viewed as a string, e.g. an enum implementing
+/// `AsRef<str>`, as [Type::String]:
+///
+/// ```
+/// use esql::{query, text};
+///
+/// let role: std::rc::Rc<str> = "admin".into();
+/// let q = query(("SELECT * FROM users WHERE role = ?", text(role)));
+/// ```
+pub fn text<'a>(value: impl AsRef<str>) -> Type<'a> {
+    Type::String(Cow::Owned(value.as_ref().to_owned()))
+}
+
+/// A `NULL` that is sent with the given Postgres type. Use it where the server
+/// cannot infer the type of a parameter, e.g. in `? IS NULL`:
+///
+/// ```
+/// use esql::{query, typed_null};
+/// use tokio_postgres::types::Type;
+///
+/// let q = query(("SELECT ? IS NULL", typed_null(Type::INT4)));
+/// ```
+#[cfg(feature = "tokio-postgres")]
+pub fn typed_null<'a>(ty: tokio_postgres::types::Type) -> Type<'a> {
+    Type::TypedNull(ty)
+}
+
+/// A Postgres `interval`. Months, days and microseconds are kept separately,
+/// like Postgres does, as the length of a month or a day depends on the date
+/// the interval gets applied to.
+#[cfg(feature = "tokio-postgres")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub microseconds: i64,
+}
+
+#[cfg(feature = "tokio-postgres")]
+impl<'a> From<Interval> for Type<'a> {
+    fn from(value: Interval) -> Self {
+        Type::Interval(value)
+    }
+}
+
+#[cfg(feature = "tokio-postgres")]
+impl<'de> serde::Deserialize<'de> for Interval {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IntervalVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for IntervalVisitor {
+            type Value = Interval;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a sequence of months, days and microseconds")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Interval, A::Error> {
+                use serde::de::Error;
+
+                Ok(Interval {
+                    months: seq
+                        .next_element()?
+                        .ok_or_else(|| A::Error::invalid_length(0, &self))?,
+                    days: seq
+                        .next_element()?
+                        .ok_or_else(|| A::Error::invalid_length(1, &self))?,
+                    microseconds: seq
+                        .next_element()?
+                        .ok_or_else(|| A::Error::invalid_length(2, &self))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(3, IntervalVisitor)
+    }
+}
+
+macro_rules! nonzero_args {
+    ($($source:ident => $target:ident,)+) => {
+        $(impl<'a> From<std::num::$source> for Type<'a> {
+            fn from(value: std::num::$source) -> Self {
+                Type::$target(value.get())
+            }
+        })+
+    };
+}
+
+nonzero_args! {
+    NonZeroI8 => Int8,
+    NonZeroI16 => Int16,
+    NonZeroI32 => Int32,
+    NonZeroI64 => Int64,
+    NonZeroIsize => Isize,
+    NonZeroU8 => UInt8,
+    NonZeroU16 => UInt16,
+    NonZeroU32 => UInt32,
+    NonZeroU64 => UInt64,
+    NonZeroUsize => Usize,
+}
+
+/// `None` is bound as SQL `NULL`, no matter how deeply the option is nested or
+/// which type it wraps. This also applies to JSON columns: to store a JSON
+/// `null` instead of SQL `NULL`, bind `serde_json::Value::Null`.
 impl<'a, A: Into<Type<'a>>> Into<Type<'a>> for Option<A> {
     fn into(self) -> Type<'a> {
         match self {
@@ -65,12 +235,78 @@ impl<'a> Into<Type<'a>> for &'a str {
     }
 }
 
+impl<'a> From<Cow<'a, str>> for Type<'a> {
+    fn from(value: Cow<'a, str>) -> Self {
+        Type::String(value)
+    }
+}
+
+impl<'a> From<&'a String> for Type<'a> {
+    fn from(value: &'a String) -> Self {
+        Type::String(value.as_str().into())
+    }
+}
+
+/// A `char` is bound as a one-character string, which Postgres accepts for
+/// `text`, `varchar` and `char(1)` parameters.
+impl<'a> From<char> for Type<'a> {
+    fn from(value: char) -> Self {
+        Type::String(value.to_string().into())
+    }
+}
+
 impl<'a> Into<Type<'a>> for String {
     fn into(self) -> Type<'a> {
         Type::String(self.into())
     }
 }
 
+/// Byte slices and arrays are bound as `bytea`, e.g. for hashes and keys.
+/// Note that a `Vec<u8>` is bound as an array of integers instead.
+impl<'a> From<&'a [u8]> for Type<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        Type::Bytes(Cow::Borrowed(value))
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for Type<'a> {
+    fn from(value: &'a [u8; N]) -> Self {
+        Type::Bytes(Cow::Borrowed(value))
+    }
+}
+
+impl<'a, const N: usize> From<[u8; N]> for Type<'a> {
+    fn from(value: [u8; N]) -> Self {
+        Type::Bytes(Cow::Owned(value.to_vec()))
+    }
+}
+
+/// Bound as a Postgres `timestamptz`, which also covers times before the
+/// Unix epoch.
+impl<'a> From<std::time::SystemTime> for Type<'a> {
+    fn from(value: std::time::SystemTime) -> Self {
+        Type::SystemTime(value)
+    }
+}
+
+/// Binds the values as a single array parameter, e.g. for `tags && ?` or
+/// `id = ANY(?)`. `None` elements become `NULL` entries.
+/// The element type is taken from the parameter type the server infers, so
+/// all values should be of the same kind. Wrap mixed values in `Json` to bind
+/// them as a JSON array instead.
+impl<'a, T: Into<Type<'a>>> From<Vec<T>> for Type<'a> {
+    fn from(value: Vec<T>) -> Self {
+        Type::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl<'a> From<rust_decimal::Decimal> for Type<'a> {
+    fn from(value: rust_decimal::Decimal) -> Self {
+        Type::Decimal(value)
+    }
+}
+
 #[cfg(feature = "serde-json")]
 impl<'a> Into<Type<'a>> for serde_json::Value {
     fn into(self) -> Type<'a> {
@@ -78,6 +314,38 @@ impl<'a> Into<Type<'a>> for serde_json::Value {
     }
 }
 
+/// Binds any serializable value as JSON:
+///
+/// ```
+/// use esql::{query, Json, Type};
+///
+/// #[derive(serde::Serialize)]
+/// struct Settings {
+///     theme: &'static str,
+/// }
+///
+/// let q = query(("UPDATE users SET settings = ?", Json(Settings { theme: "dark" })));
+/// let (_, args) = q.build(esql::ArgFormat::Indexed);
+///
+/// assert_eq!(args, [Type::Json(serde_json::json!({"theme": "dark"}))]);
+/// ```
+///
+/// A value that fails to serialize, e.g. a map with non-string keys, is bound
+/// as [Type::Invalid], so executing the query returns an error.
+#[cfg(feature = "serde-json")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "serde-json")]
+impl<'a, T: serde::Serialize> From<Json<T>> for Type<'a> {
+    fn from(value: Json<T>) -> Self {
+        match serde_json::to_value(value.0) {
+            Ok(value) => Type::Json(value),
+            Err(e) => Type::Invalid(e.to_string()),
+        }
+    }
+}
+
 #[cfg(feature = "time")]
 impl<'a> Into<Type<'a>> for time::OffsetDateTime {
     fn into(self) -> Type<'a> {
@@ -85,9 +353,39 @@ impl<'a> Into<Type<'a>> for time::OffsetDateTime {
     }
 }
 
+#[cfg(feature = "time")]
+impl<'a> From<&time::OffsetDateTime> for Type<'a> {
+    fn from(value: &time::OffsetDateTime) -> Self {
+        Type::OffsetDateTime(*value)
+    }
+}
+
+/// Bound as a Postgres `interval`. As intervals have a resolution of one
+/// microsecond, the nanoseconds are truncated.
+#[cfg(feature = "time")]
+impl<'a> From<time::Duration> for Type<'a> {
+    fn from(value: time::Duration) -> Self {
+        Type::Duration(value)
+    }
+}
+
+#[cfg(feature = "time")]
+impl<'a> From<&time::Duration> for Type<'a> {
+    fn from(value: &time::Duration) -> Self {
+        Type::Duration(*value)
+    }
+}
+
 #[cfg(feature = "uuid")]
 impl<'a> Into<Type<'a>> for uuid::Uuid {
     fn into(self) -> Type<'a> {
         Type::Uuid(self)
     }
 }
+
+#[cfg(feature = "uuid")]
+impl<'a> From<&uuid::Uuid> for Type<'a> {
+    fn from(value: &uuid::Uuid) -> Self {
+        Type::Uuid(*value)
+    }
+}
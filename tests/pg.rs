@@ -0,0 +1,1289 @@
+//! Integration tests for the tokio-postgres backend. They expect a running
+//! database that can be reached via `DATABASE_URL`.
+#![cfg(feature = "tokio-postgres")]
+
+use esql::{copy_in, query, PgFromRow, PgQueryExt, Type};
+use serde::Deserialize;
+use tokio_postgres::{Client, NoTls, Row};
+
+async fn connect() -> Client {
+    let url = std::env::var("DATABASE_URL")
+        .unwrap_or_else(|_| String::from("host=localhost user=postgres"));
+
+    let (client, connection) = tokio_postgres::connect(&url, NoTls).await.unwrap();
+    tokio::spawn(connection);
+
+    client
+}
+
+#[cfg(feature = "serde-json")]
+#[tokio::test]
+async fn explain() {
+    let client = connect().await;
+    let q = || query(("SELECT * FROM generate_series(1, ?::int) n", 10)).wh(("n > ?", 5));
+
+    let plan = q().explain(&client).await.unwrap();
+
+    assert!(plan[0]["Plan"]["Node Type"].is_string());
+    assert!(plan[0]["Plan"].get("Actual Rows").is_none());
+
+    let plan = q().explain_analyze(&client).await.unwrap();
+
+    assert!(plan[0]["Plan"]["Node Type"].is_string());
+    assert_eq!(plan[0]["Plan"]["Actual Rows"], 5);
+    assert!(plan[0]["Execution Time"].is_number());
+}
+
+#[tokio::test]
+async fn unsupported_column_type() {
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Row {
+        location: String,
+    }
+
+    let client = connect().await;
+
+    let result = query("SELECT point(1, 2) AS location")
+        .get::<Row>(&client)
+        .await;
+
+    // 600 is the oid of point.
+    assert!(matches!(result, Err(esql::Error::UnsupportedType(600))));
+}
+
+#[tokio::test]
+async fn string_like_columns() {
+    #[derive(Deserialize)]
+    struct Row {
+        table_name: String,
+        document: String,
+    }
+
+    let client = connect().await;
+
+    let row: Row = query(
+        "SELECT tablename AS table_name, '<a>b</a>'::xml AS document
+        FROM pg_catalog.pg_tables",
+    )
+    .wh("schemaname = 'pg_catalog'")
+    .and("tablename = 'pg_class'")
+    .first_required(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(row.table_name, "pg_class");
+    assert_eq!(row.document, "<a>b</a>");
+}
+
+#[tokio::test]
+async fn char_column() {
+    #[derive(Deserialize)]
+    struct Row {
+        relkind: char,
+        kind: String,
+    }
+
+    let client = connect().await;
+
+    let row: Row = query("SELECT relkind, relkind AS kind FROM pg_catalog.pg_class")
+        .wh("relname = 'pg_class'")
+        .first_required(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(row.relkind, 'r');
+    assert_eq!(row.kind, "r");
+}
+
+#[cfg(feature = "serde-json")]
+#[tokio::test]
+async fn json_columns() {
+    use serde_json::{json, Value};
+
+    #[derive(Deserialize)]
+    struct Row {
+        document: Value,
+        json_null: Option<Value>,
+        sql_null: Option<Value>,
+    }
+
+    let client = connect().await;
+
+    let row: Row = query(
+        r#"SELECT '{"a": [1, {"b": null}], "c": "d"}'::jsonb AS document,
+        'null'::json AS json_null,
+        NULL::jsonb AS sql_null"#,
+    )
+    .first_required(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(row.document, json!({"a": [1, {"b": null}], "c": "d"}));
+    assert_eq!(row.json_null, Some(Value::Null));
+    assert_eq!(row.sql_null, None);
+}
+
+#[cfg(feature = "serde-json")]
+#[tokio::test]
+async fn json_binding() {
+    use std::collections::BTreeMap;
+
+    use esql::Json;
+    use serde::Serialize;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Settings {
+        theme: String,
+        tabs: Vec<u8>,
+    }
+
+    #[derive(Deserialize)]
+    struct Row {
+        settings: serde_json::Value,
+    }
+
+    let client = connect().await;
+    let settings = Settings {
+        theme: String::from("dark"),
+        tabs: vec![2, 4],
+    };
+
+    let row: Row = query(("SELECT ?::jsonb AS settings", Json(&settings)))
+        .first_required(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        serde_json::from_value::<Settings>(row.settings).unwrap(),
+        settings
+    );
+
+    let invalid = BTreeMap::from([((1, 2), "tuple keys are not valid JSON")]);
+
+    let result = query(("SELECT ?::jsonb", Json(invalid)))
+        .execute(&client)
+        .await;
+
+    assert!(matches!(result, Err(esql::Error::PostgresError(_))));
+}
+
+#[cfg(feature = "serde-json")]
+#[tokio::test]
+async fn raw_json_binding() {
+    use std::borrow::Cow;
+
+    let client = connect().await;
+    let document = br#"{"theme": "dark", "tabs": [2, 4]}"#;
+
+    let (jsonb, json): (serde_json::Value, String) = query((
+        "SELECT ?::jsonb, ?::json::text",
+        Type::JsonRaw(Cow::Borrowed(document)),
+        Type::JsonRaw(Cow::Borrowed(document)),
+    ))
+    .first_required(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(jsonb, serde_json::json!({"theme": "dark", "tabs": [2, 4]}));
+    assert_eq!(json, r#"{"theme": "dark", "tabs": [2, 4]}"#);
+
+    let result = query(("SELECT ?::jsonb", Type::JsonRaw(Cow::Borrowed(b"{"))))
+        .execute(&client)
+        .await;
+
+    assert!(matches!(result, Err(esql::Error::PostgresError(_))));
+}
+
+#[cfg(feature = "serde-json")]
+#[tokio::test]
+async fn mixed_json_array() {
+    use esql::Json;
+
+    let client = connect().await;
+    let mixed = serde_json::json!([1, "two", true, null]);
+
+    let kinds: Vec<String> = query((
+        "SELECT jsonb_typeof(value) FROM jsonb_array_elements(?::jsonb)",
+        Json(&mixed),
+    ))
+    .values(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(kinds, ["number", "string", "boolean", "null"]);
+}
+
+#[tokio::test]
+async fn pointer_sized_integers() {
+    #[derive(Deserialize)]
+    struct Row {
+        small: isize,
+        big: isize,
+        size: usize,
+    }
+
+    let client = connect().await;
+
+    let row: Row = query((
+        "SELECT ?::int4 AS small, ?::int8 AS big, ?::int8 AS size",
+        Type::Isize(-5),
+        Type::Isize(isize::MIN),
+        Type::Usize(usize::MAX >> 1),
+    ))
+    .first_required(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(row.small, -5);
+    assert_eq!(row.big, isize::MIN);
+    assert_eq!(row.size, usize::MAX >> 1);
+
+    let negative = query("SELECT -1::int8 AS small, 0::int8 AS big, -1::int8 AS size")
+        .get::<Row>(&client)
+        .await;
+
+    assert!(matches!(negative, Err(esql::Error::FromRowError { .. })));
+
+    let overflow = query(("SELECT ?::int8", Type::Usize(usize::MAX)))
+        .execute(&client)
+        .await;
+
+    assert!(matches!(overflow, Err(esql::Error::PostgresError(_))));
+}
+
+#[tokio::test]
+async fn positional_deserialization() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Row(i32, String, Option<bool>);
+
+    let client = connect().await;
+
+    let rows: Vec<Row> = query("SELECT 1, 'one', NULL::bool UNION ALL SELECT 2, 'two', true")
+        .get(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        rows,
+        [
+            Row(1, String::from("one"), None),
+            Row(2, String::from("two"), Some(true))
+        ]
+    );
+
+    // Both columns are named `?column?`, so only the position tells them apart
+    let pair: Option<(i32, i32)> = query("SELECT 3, 4").first(&client).await.unwrap();
+
+    assert_eq!(pair, Some((3, 4)));
+}
+
+#[tokio::test]
+async fn rows() {
+    let client = connect().await;
+
+    let rows = query(("SELECT generate_series(1, ?)", 5))
+        .rows(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(rows.len(), 5);
+    assert_eq!(rows[4].get::<_, i32>(0), 5);
+}
+
+#[tokio::test]
+async fn map_rows() {
+    let client = connect().await;
+
+    let areas = query("SELECT * FROM (VALUES (2, 3), (4, 5)) AS t (width, height)")
+        .map_rows(&client, |row| {
+            Ok::<_, tokio_postgres::Error>(
+                row.try_get::<_, i32>("width")? * row.try_get::<_, i32>("height")?,
+            )
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(areas, [6, 20]);
+}
+
+#[tokio::test]
+async fn scalar_opt() {
+    let client = connect().await;
+
+    let none: Option<i32> = query("SELECT 1 WHERE false")
+        .scalar_opt(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(none, None);
+
+    let one: Option<i32> = query("SELECT 1").scalar_opt(&client).await.unwrap();
+
+    assert_eq!(one, Some(1));
+
+    let many = query("SELECT generate_series(1, 3)")
+        .scalar_opt::<i32>(&client)
+        .await;
+
+    assert!(matches!(
+        many,
+        Err(esql::Error::UnexpectedRowCount {
+            expected: 1,
+            got: 3,
+            ..
+        })
+    ));
+}
+
+#[tokio::test]
+async fn first_required() {
+    #[derive(Deserialize)]
+    struct Row {
+        id: i32,
+    }
+
+    let client = connect().await;
+
+    let row: Row = query("SELECT 7 AS id")
+        .first_required(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(row.id, 7);
+
+    let missing = query("SELECT 7 AS id WHERE false")
+        .first_required::<Row>(&client)
+        .await;
+
+    assert!(matches!(missing, Err(esql::Error::NotFound)));
+}
+
+#[tokio::test]
+async fn copy_in_rows() {
+    let client = connect().await;
+
+    query("CREATE TEMPORARY TABLE copied (id int, name text, note text, tags int4[])")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    let copied = copy_in(
+        &client,
+        "copied",
+        ["id", "name", "note", "tags"],
+        (0..1000).map(|i| {
+            [
+                Type::Int32(i),
+                Type::String(format!("row\t{i}\n\\").into()),
+                if i % 2 == 0 { Type::Null } else { "odd".into() },
+                vec![Some(i), None].into(),
+            ]
+        }),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(copied, 1000);
+
+    let (name, note, tags): (String, Option<String>, Vec<Option<i32>>) = {
+        let row = query(("SELECT name, note, tags FROM copied WHERE id = ?", 42))
+            .rows(&client)
+            .await
+            .unwrap()
+            .remove(0);
+
+        (row.get(0), row.get(1), row.get(2))
+    };
+
+    assert_eq!(name, "row\t42\n\\");
+    assert_eq!(note, None);
+    assert_eq!(tags, [Some(42), None]);
+
+    let nulls: Option<i64> = query("SELECT count(*) FROM copied WHERE note IS NULL")
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(nulls, Some(500));
+
+    let mismatch = copy_in(&client, "copied", ["id", "name"], [[1]]).await;
+
+    assert!(matches!(
+        mismatch,
+        Err(esql::Error::RowArity {
+            row: 0,
+            expected: 2,
+            got: 1
+        })
+    ));
+}
+
+#[cfg(feature = "qp-postgres")]
+#[tokio::test]
+async fn into_stream_boxed() {
+    use futures_util::TryStreamExt as _;
+
+    #[derive(Deserialize)]
+    struct Row {
+        id: i32,
+    }
+
+    let url = std::env::var("DATABASE_URL")
+        .unwrap_or_else(|_| String::from("host=localhost user=postgres"));
+    let pool = qp_postgres::connect(url.parse().unwrap(), NoTls, 1);
+
+    let rows = query(("SELECT generate_series(1, ?) AS id", 100))
+        .into_stream_boxed::<Row, _>(&pool)
+        .await
+        .unwrap();
+
+    // The only connection stays checked out while the rows are pending.
+    assert_eq!(pool.size(), 0);
+
+    let sum = tokio::spawn(async move {
+        rows.try_fold(0, |sum, row| async move { Ok(sum + row.id) })
+            .await
+            .unwrap()
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(sum, 5050);
+    assert_eq!(pool.size(), 1);
+
+    let mut rows = query(("SELECT generate_series(1, ?) AS id", 100))
+        .into_stream_boxed::<Row, _>(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(rows.try_next().await.unwrap().unwrap().id, 1);
+    drop(rows);
+    assert_eq!(pool.size(), 1);
+
+    let result = query("SELECT * FROM missing_table")
+        .into_stream_boxed::<Row, _>(&pool)
+        .await;
+
+    assert!(matches!(result, Err(esql::Error::PostgresError(_))));
+    assert_eq!(pool.size(), 1);
+}
+
+#[tokio::test]
+async fn secret_binding() {
+    let client = connect().await;
+
+    let token: Option<String> = query(("SELECT ?::text", esql::secret("abc123")))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(token.as_deref(), Some("abc123"));
+}
+
+#[tokio::test]
+async fn rebind() {
+    let client = connect().await;
+    let template = query(("SELECT ? * 2", 0));
+
+    for n in [2, 21] {
+        let doubled: Option<i32> = template
+            .clone()
+            .rebind(vec![Type::Int32(n)])
+            .unwrap()
+            .value(&client)
+            .await
+            .unwrap();
+
+        assert_eq!(doubled, Some(n * 2));
+    }
+}
+
+#[tokio::test]
+async fn execute_returning_id() {
+    let client = connect().await;
+
+    query("CREATE TEMPORARY TABLE returning_ids (id bigserial PRIMARY KEY, name text)")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    for expected in [1, 2] {
+        let id: i64 = query(("INSERT INTO returning_ids (name) VALUES (?)", "a"))
+            .execute_returning_id(&client)
+            .await
+            .unwrap();
+
+        assert_eq!(id, expected);
+    }
+}
+
+#[tokio::test]
+async fn update_returning_rows() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Account {
+        id: i32,
+        balance: i64,
+    }
+
+    let client = connect().await;
+
+    query("CREATE TEMPORARY TABLE accounts (id int, balance int8)")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    query("INSERT INTO accounts VALUES (1, 10), (2, 20), (3, 30)")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    let mut updated: Vec<Account> = (query(("UPDATE accounts SET balance = balance + ?", 5i64))
+        .wh(("id >= ?", 2))
+        + "RETURNING *")
+        .get(&client)
+        .await
+        .unwrap();
+
+    updated.sort_by_key(|account| account.id);
+
+    assert_eq!(
+        updated,
+        [
+            Account { id: 2, balance: 25 },
+            Account { id: 3, balance: 35 }
+        ]
+    );
+}
+
+#[tokio::test]
+async fn get_map() {
+    #[derive(Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    let client = connect().await;
+
+    let users: std::collections::HashMap<i32, User> =
+        query("SELECT * FROM (VALUES (1, 'alice'), (2, 'bob')) AS t (id, name)")
+            .get_map(&client, "id")
+            .await
+            .unwrap();
+
+    assert_eq!(users.len(), 2);
+    assert_eq!(users[&2].name, "bob");
+
+    let duplicate = query("SELECT * FROM (VALUES (1, 'alice'), (1, 'bob')) AS t (id, name)")
+        .get_map::<i32, User>(&client, "id")
+        .await;
+
+    assert!(matches!(duplicate, Err(esql::Error::DuplicateKey(column)) if column == "id"));
+}
+
+#[tokio::test]
+async fn get_positional() {
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: i32,
+        name: String,
+    }
+
+    impl PgFromRow for User {
+        fn from_row(row: &Row) -> Result<Self, tokio_postgres::Error> {
+            Ok(User {
+                id: row.try_get(0)?,
+                name: row.try_get(1)?,
+            })
+        }
+    }
+
+    let client = connect().await;
+    let sql = "SELECT * FROM (VALUES (1, 'alice'), (2, 'bob')) AS t (renamed, whatever)";
+
+    let users: Vec<User> = query(sql).get_positional(&client).await.unwrap();
+
+    assert_eq!(
+        users,
+        [
+            User {
+                id: 1,
+                name: String::from("alice")
+            },
+            User {
+                id: 2,
+                name: String::from("bob")
+            }
+        ]
+    );
+
+    let tuples: Vec<(i32, String)> = query(sql).get_positional(&client).await.unwrap();
+
+    assert_eq!(tuples[1], (2, String::from("bob")));
+
+    let mismatch = query(sql).get_positional::<(i32, i32)>(&client).await;
+
+    assert!(matches!(mismatch, Err(esql::Error::PostgresError(_))));
+}
+
+#[tokio::test]
+async fn timeout() {
+    use std::time::{Duration, Instant};
+
+    let client = connect().await;
+    let started = Instant::now();
+
+    let result = query("SELECT pg_sleep(5)")
+        .execute_timeout(&client, Duration::from_millis(100))
+        .await;
+
+    assert!(matches!(result, Err(esql::Error::Timeout)));
+
+    // The connection is only usable this fast if the statement got cancelled
+    let one: Option<i32> = query("SELECT 1").value(&client).await.unwrap();
+
+    assert_eq!(one, Some(1));
+    assert!(started.elapsed() < Duration::from_secs(3));
+
+    let rows = query("SELECT pg_sleep(5)")
+        .get_timeout::<()>(&client, Duration::from_millis(100))
+        .await;
+
+    assert!(matches!(rows, Err(esql::Error::Timeout)));
+}
+
+#[tokio::test]
+async fn query_observer() {
+    use std::sync::Mutex;
+
+    use esql::{QueryEvent, QueryObserver};
+
+    static EVENTS: Mutex<Vec<(String, Option<u64>, bool)>> = Mutex::new(Vec::new());
+
+    struct Recorder;
+
+    impl QueryObserver for Recorder {
+        fn on_query(&self, event: &QueryEvent<'_>) {
+            if event.sql.contains("observed") {
+                EVENTS
+                    .lock()
+                    .unwrap()
+                    .push((event.sql.to_string(), event.rows, event.success));
+            }
+        }
+    }
+
+    assert!(esql::set_query_observer(Recorder));
+
+    let client = connect().await;
+
+    query("CREATE TEMPORARY TABLE observed (id int)")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    query(("INSERT INTO observed VALUES (?), (?)", 1, 2))
+        .execute(&client)
+        .await
+        .unwrap();
+
+    query("SELECT * FROM observed").rows(&client).await.unwrap();
+    query("SELECT * FROM observed_missing")
+        .rows(&client)
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        [
+            (
+                String::from("CREATE TEMPORARY TABLE observed (id int)"),
+                Some(0),
+                true
+            ),
+            (
+                String::from("INSERT INTO observed VALUES ($1), ($2)"),
+                Some(2),
+                true
+            ),
+            (String::from("SELECT * FROM observed"), None, true),
+            (String::from("SELECT * FROM observed_missing"), None, false),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn copy_out() {
+    use futures_util::TryStreamExt as _;
+
+    let client = connect().await;
+
+    let data: Vec<u8> = query("SELECT id, 'row ' || id FROM generate_series(1, 300) AS id")
+        .copy_out(&client, "")
+        .await
+        .unwrap()
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .unwrap();
+
+    let data = String::from_utf8(data).unwrap();
+
+    assert_eq!(data.lines().count(), 300);
+    assert_eq!(data.lines().last(), Some("300\trow 300"));
+
+    let csv: Vec<u8> = query("SELECT 1 AS a, 2 AS b")
+        .copy_out(&client, "(FORMAT csv, HEADER)")
+        .await
+        .unwrap()
+        .map_ok(|chunk| chunk.to_vec())
+        .try_concat()
+        .await
+        .unwrap();
+
+    assert_eq!(csv, b"a,b\n1,2\n");
+
+    let with_args = query(("SELECT ?", 1)).copy_out(&client, "").await;
+
+    assert!(matches!(
+        with_args,
+        Err(esql::Error::InvalidArgument(message)) if message == "COPY does not support bind parameters"
+    ));
+}
+
+#[cfg(feature = "serde-json")]
+#[tokio::test]
+async fn estimated_cost() {
+    let client = connect().await;
+
+    let cost = query(("SELECT * FROM generate_series(1, ?::int)", 100))
+        .estimated_cost(&client)
+        .await
+        .unwrap();
+
+    assert!(cost > 0.0);
+
+    let ddl = query("CREATE TABLE never_created (id int)")
+        .estimated_cost(&client)
+        .await;
+
+    assert!(matches!(ddl, Err(esql::Error::PostgresError(_))));
+}
+
+#[tokio::test]
+async fn integer_coercion() {
+    let client = connect().await;
+
+    let values = [
+        (Type::Int32(0), "0"),
+        (Type::Int32(-42), "-42"),
+        (Type::Int64(1_234_500_000_000), "1234500000000"),
+        (Type::UInt64(u64::MAX), "18446744073709551615"),
+    ];
+
+    for (value, expected) in values {
+        let text: Option<String> = query(("SELECT ?::numeric::text", value))
+            .value(&client)
+            .await
+            .unwrap();
+
+        assert_eq!(text.as_deref(), Some(expected));
+    }
+
+    let wide: Option<i64> = query(("SELECT ?::int8", Type::Int16(7)))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(wide, Some(7));
+
+    let narrow: Option<i16> = query(("SELECT ?::int2", Type::Int64(300)))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(narrow, Some(300));
+
+    let overflow = query(("SELECT ?::int2", Type::Int64(1 << 20)))
+        .execute(&client)
+        .await;
+
+    assert!(matches!(overflow, Err(esql::Error::PostgresError(_))));
+}
+
+#[tokio::test]
+async fn typed_null() {
+    let client = connect().await;
+
+    let untyped = query(("SELECT ? IS NULL", Type::Null))
+        .value::<bool>(&client)
+        .await;
+
+    assert!(matches!(untyped, Err(esql::Error::PostgresError(_))));
+
+    let typed: Option<bool> = query((
+        "SELECT ? IS NULL AND ? = 2",
+        esql::typed_null(tokio_postgres::types::Type::INT4),
+        2,
+    ))
+    .value(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(typed, Some(true));
+}
+
+#[tokio::test]
+async fn byte_array_binding() {
+    let client = connect().await;
+    let hash: [u8; 16] = *b"0123456789abcdef";
+
+    let bytes: Option<Vec<u8>> = query(("SELECT ?::bytea", hash))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(bytes.as_deref(), Some(&hash[..]));
+
+    let length: Option<i32> = query(("SELECT length(?::bytea)", &hash))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(length, Some(16));
+
+    query("CREATE TEMPORARY TABLE hashes (hash bytea)")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    copy_in(&client, "hashes", ["hash"], [[Type::from(hash)]])
+        .await
+        .unwrap();
+
+    let copied: Option<Vec<u8>> = query("SELECT hash FROM hashes")
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(copied.as_deref(), Some(&hash[..]));
+}
+
+#[cfg(feature = "time")]
+#[tokio::test]
+async fn duration_binding() {
+    #[derive(Deserialize)]
+    struct Row {
+        duration: time::Duration,
+        text: String,
+        optional: Option<time::Duration>,
+    }
+
+    let client = connect().await;
+
+    for (duration, text) in [
+        (time::Duration::new(93_784, 5_000), "26:03:04.000005"),
+        (time::Duration::new(-90, -500_000_000), "-00:01:30.5"),
+    ] {
+        let row: Row = query((
+            "SELECT ?::interval AS duration, ?::interval::text AS text, NULL::interval AS optional",
+            duration,
+            duration,
+        ))
+        .first_required(&client)
+        .await
+        .unwrap();
+
+        assert_eq!(row.duration, duration);
+        assert_eq!(row.text, text);
+        assert_eq!(row.optional, None);
+    }
+
+    let months = query("SELECT '1 month'::interval AS duration, '' AS text, NULL AS optional")
+        .get::<Row>(&client)
+        .await;
+
+    assert!(matches!(months, Err(esql::Error::FromRowError { .. })));
+}
+
+#[cfg(feature = "time")]
+#[tokio::test]
+async fn duration_out_of_range() {
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Row {
+        duration: time::Duration,
+    }
+
+    let client = connect().await;
+
+    let result = query("SELECT '200000000 days'::interval AS duration")
+        .get::<Row>(&client)
+        .await;
+
+    assert!(matches!(result, Err(esql::Error::FromRowError { .. })));
+}
+
+#[tokio::test]
+async fn system_time_binding() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    let client = connect().await;
+
+    // Postgres stores microseconds, so compare at that resolution
+    let now = UNIX_EPOCH
+        + Duration::from_micros(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_micros() as u64,
+        );
+    let before_epoch = UNIX_EPOCH - Duration::from_micros(86_400_000_000 * 365 + 1_500);
+    // 0001-01-01 is 62135596800 seconds before the epoch, so this is 2 BC
+    let before_christ = UNIX_EPOCH - Duration::from_secs(62_135_596_800 + 86_400 * 400);
+
+    for time in [now, before_epoch, before_christ] {
+        let read: Option<SystemTime> = query(("SELECT ?::timestamptz", time))
+            .value(&client)
+            .await
+            .unwrap();
+
+        assert_eq!(read, Some(time));
+    }
+
+    query("CREATE TEMPORARY TABLE times (at timestamptz)")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    copy_in(
+        &client,
+        "times",
+        ["at"],
+        [
+            [Type::from(before_epoch)],
+            [Type::from(now)],
+            [Type::from(before_christ)],
+        ],
+    )
+    .await
+    .unwrap();
+
+    let copied: Vec<SystemTime> = query("SELECT at FROM times").values(&client).await.unwrap();
+
+    assert_eq!(copied, [before_epoch, now, before_christ]);
+
+    let text: Option<String> = query((
+        "SELECT (?::timestamptz AT TIME ZONE 'UTC')::text",
+        before_epoch,
+    ))
+    .value(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(text.as_deref(), Some("1968-12-31 23:59:59.9985"));
+
+    let text: Option<String> = query((
+        "SELECT (?::timestamptz AT TIME ZONE 'UTC')::text",
+        before_christ,
+    ))
+    .value(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(text.as_deref(), Some("0002-11-28 00:00:00 BC"));
+}
+
+#[tokio::test]
+async fn execute_with_retry() {
+    let client = connect().await;
+
+    query("CREATE TEMPORARY SEQUENCE attempts")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    // Sequences are not transactional, so the counter survives the failures
+    let conflict = query(
+        "DO $$ BEGIN
+            IF nextval('attempts') < 3 THEN
+                RAISE EXCEPTION 'conflict' USING ERRCODE = 'serialization_failure';
+            END IF;
+        END $$",
+    );
+    let policy = esql::RetryPolicy {
+        max_retries: 1,
+        backoff: std::time::Duration::from_millis(1),
+        ..Default::default()
+    };
+
+    let failed = conflict.clone().execute_with_retry(&client, policy).await;
+
+    assert!(matches!(failed, Err(esql::Error::PostgresError(_))));
+
+    // Doubling the backoff this often would overflow a Duration
+    let policy = esql::RetryPolicy {
+        max_retries: 80,
+        backoff: std::time::Duration::from_secs(1),
+        max_backoff: std::time::Duration::from_micros(1),
+    };
+
+    let failed = query(
+        "DO $$ BEGIN
+            RAISE EXCEPTION 'conflict' USING ERRCODE = 'serialization_failure';
+        END $$",
+    )
+    .execute_with_retry(&client, policy)
+    .await;
+
+    assert!(matches!(failed, Err(esql::Error::PostgresError(_))));
+
+    query("SELECT setval('attempts', 1, false)")
+        .execute(&client)
+        .await
+        .unwrap();
+
+    conflict
+        .execute_with_retry(&client, Default::default())
+        .await
+        .unwrap();
+
+    let attempts: Option<i64> = query("SELECT currval('attempts')")
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(attempts, Some(3));
+}
+
+#[tokio::test]
+async fn tablesample() {
+    let client = connect().await;
+
+    let sampled: Option<i64> = query("SELECT count(*) FROM pg_class")
+        .tablesample("BERNOULLI", 100.0)
+        .scalar_opt(&client)
+        .await
+        .unwrap();
+
+    assert!(sampled.unwrap() > 0);
+}
+
+#[tokio::test]
+async fn first_value() {
+    let client = connect().await;
+    let q = || query("SELECT n, n * 10 AS tens FROM generate_series(3, 5) n ORDER BY n");
+
+    let tens: Option<i32> = q().first_value(&client, "tens").await.unwrap();
+    assert_eq!(tens, Some(30));
+
+    let missing: Option<i32> = (q() + "LIMIT 0")
+        .first_value(&client, "tens")
+        .await
+        .unwrap();
+    assert_eq!(missing, None);
+
+    let unknown = q().first_value::<i32, _>(&client, "hundreds").await;
+    assert!(matches!(
+        unknown,
+        Err(esql::Error::FromRowError { sql: Some(sql) }) if sql == q().sql()
+    ));
+}
+
+#[tokio::test]
+async fn count_over() {
+    let client = connect().await;
+
+    let page = query("SELECT n FROM generate_series(1, 50) n")
+        .wh(("n % ? = 0", 2))
+        .paginate(2, 10)
+        .unwrap();
+
+    let rows: Vec<i32> = page.clone().values(&client).await.unwrap();
+    assert_eq!(rows, (22..=40).step_by(2).collect::<Vec<_>>());
+
+    assert_eq!(page.count_over(&client).await.unwrap(), 25);
+}
+
+#[tokio::test]
+async fn array_binding() {
+    let client = connect().await;
+
+    let ids: Vec<i32> = query(
+        "WITH t (id, tags) AS (VALUES (1, ARRAY[1, 2]), (2, ARRAY[3]), (3, '{}'::int4[]))
+        SELECT id FROM t",
+    )
+    .wh(("tags && ?", vec![2, 3]))
+    .values(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(ids, [1, 2]);
+
+    let tags: Option<Vec<Option<i32>>> = query(("SELECT ?::int4[]", vec![Some(1), None]))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(tags, Some(vec![Some(1), None]));
+
+    let flags: Option<Vec<bool>> = query(("SELECT ?::bool[]", vec![true, false]))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(flags, Some(vec![true, false]));
+
+    let names: Option<Vec<String>> = query(("SELECT ?::text[]", vec!["a", "b"]))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(names, Some(vec![String::from("a"), String::from("b")]));
+
+    let big: Option<Vec<i64>> = query(("SELECT ?::int8[]", vec![1i64 << 40]))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(big, Some(vec![1i64 << 40]));
+
+    let none: Vec<i32> = query("SELECT * FROM generate_series(1, 3) AS id")
+        .wh(esql::any("id", Vec::<i32>::new()))
+        .values(&client)
+        .await
+        .unwrap();
+
+    assert!(none.is_empty());
+
+    let some: Vec<i32> = query("SELECT * FROM generate_series(1, 3) AS id")
+        .wh(esql::any("id", vec![1, 3]))
+        .values(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(some, [1, 3]);
+
+    let empty: Option<Vec<i32>> = query(("SELECT ?::int4[]", Vec::<i32>::new()))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(empty, Some(vec![]));
+}
+
+#[tokio::test]
+async fn interval_round_trip() {
+    use esql::Interval;
+
+    #[derive(Deserialize)]
+    struct Row {
+        span: Interval,
+        text: String,
+    }
+
+    let client = connect().await;
+    let span = Interval {
+        months: 1,
+        days: 3,
+        microseconds: 4 * 3_600_000_000 + 500,
+    };
+
+    let row: Row = query((
+        "SELECT ?::interval AS span, ?::interval::text AS text",
+        span,
+        span,
+    ))
+    .first_required(&client)
+    .await
+    .unwrap();
+
+    assert_eq!(row.span, span);
+    assert_eq!(row.text, "1 mon 3 days 04:00:00.0005");
+
+    let doubled: Option<Interval> = query(("SELECT ?::interval * 2", span))
+        .value(&client)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        doubled,
+        Some(Interval {
+            months: 2,
+            days: 6,
+            microseconds: 8 * 3_600_000_000 + 1000,
+        })
+    );
+}
+
+#[cfg(feature = "decimal")]
+#[tokio::test]
+async fn decimal_round_trip() {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    #[derive(Deserialize)]
+    struct Row {
+        amount: Decimal,
+    }
+
+    let client = connect().await;
+    let amount = Decimal::from_str("1234.5678901234567890123456").unwrap();
+
+    let row: Row = query(("SELECT ?::numeric AS amount", amount))
+        .first(&client)
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(row.amount, amount);
+}
+
+#[cfg(feature = "decimal")]
+#[tokio::test]
+async fn decimal_out_of_range() {
+    use rust_decimal::Decimal;
+
+    #[derive(Deserialize)]
+    struct Row {
+        amount: Decimal,
+    }
+
+    #[derive(Deserialize)]
+    struct Text {
+        amount: String,
+    }
+
+    let client = connect().await;
+
+    for sql in [
+        "SELECT 'NaN'::numeric AS amount",
+        "SELECT 1e40::numeric AS amount",
+    ] {
+        let result = query(sql).first::<Row>(&client).await;
+        assert!(matches!(
+            result.map(|row| row.map(|row| row.amount)),
+            Err(esql::Error::FromRowError { .. })
+        ));
+    }
+
+    for (sql, expected) in [
+        ("SELECT 'NaN'::numeric AS amount", "NaN"),
+        (
+            "SELECT 1e40::numeric AS amount",
+            "10000000000000000000000000000000000000000",
+        ),
+        (
+            "SELECT -12345678901234567890123456789.000120::numeric AS amount",
+            "-12345678901234567890123456789.000120",
+        ),
+        (
+            "SELECT 0.00000000000000000000000000001234::numeric AS amount",
+            "0.00000000000000000000000000001234",
+        ),
+    ] {
+        let row: Text = query(sql).first_required(&client).await.unwrap();
+        assert_eq!(row.amount, expected);
+    }
+}
@@ -1,4 +1,10 @@
-use esql::{expr, in_expr, query, ArgFormat, Query, Type};
+use std::{borrow::Cow, collections::HashMap};
+
+use esql::{
+    all, all_of, any, any_of, arg, coalesce, col, ddl, expr, fields, fields_aliased, in_expr,
+    is_distinct_from, is_not_distinct_from, nullif, query, text, truncate, trusted, ArgFormat,
+    Query, QueryBuffer, Type,
+};
 
 #[test]
 fn simple_query() {
@@ -51,6 +57,1288 @@ fn query_where_in() {
     assert_query(q, "SELECT * FROM contacts WHERE 1=0", [] as [u32; 0]);
 }
 
+#[test]
+fn query_from_parts() {
+    let build = || {
+        query("SELECT * FROM users")
+            .wh(("id = ?", 1))
+            .and(("email = ?", "foo@example.com"))
+            .build(ArgFormat::Indexed)
+    };
+
+    let (sql, args) = build();
+
+    // SAFETY: the SQL was generated by the query builder
+    let q = Query::from_parts(unsafe { trusted(sql) }, args);
+
+    assert_eq!(q.build(ArgFormat::Indexed), build());
+}
+
+#[test]
+fn query_coalesce_nullif() {
+    let q = query("SELECT")
+        + coalesce([QueryBuffer::from("nickname"), arg("guest"), arg("unknown")])
+        + "FROM users";
+
+    assert_query(
+        q,
+        "SELECT COALESCE(nickname, ?, ?) FROM users",
+        ["guest", "unknown"],
+    );
+
+    let q = (query("SELECT") + nullif("status", arg("none")) + "FROM users").wh(("id = ?", "1"));
+
+    assert_query(
+        q,
+        "SELECT NULLIF(status, ?) FROM users WHERE id = ?",
+        ["none", "1"],
+    );
+}
+
+#[test]
+fn query_column_operators() {
+    let q = query("SELECT * FROM products")
+        .wh((col("price") * 2 + 10).gt(100))
+        .and(expr(col("stock").eq(0)).or((col("stock") - 5).lt(1)));
+
+    assert_query(
+        q,
+        "SELECT * FROM products WHERE (price * ?) + ? > ? AND (stock = ? OR stock - ? < ?)",
+        [2, 10, 100, 0, 5, 1],
+    );
+}
+
+#[test]
+fn query_optional_args() {
+    let name = String::from("x");
+
+    let q = query("SELECT * FROM users")
+        .wh(("deleted_at = ?", None::<i32>))
+        .and(("name = ?", Some("x")))
+        .and(("nickname = ?", Some(&name)))
+        .and(("age = ?", Some(&42)))
+        .and(("email = ?", Some(None::<String>)));
+
+    assert_eq!(
+        q.build(ArgFormat::QuestionMark).1,
+        [
+            Type::Null,
+            Type::String("x".into()),
+            Type::String("x".into()),
+            Type::Int32(42),
+            Type::Null,
+        ]
+    );
+}
+
+#[test]
+fn query_ddl() {
+    assert_query(
+        ddl("CREATE INDEX users_email ON users (email)"),
+        "CREATE INDEX users_email ON users (email)",
+        [] as [u32; 0],
+    );
+
+    assert_query(truncate("users"), "TRUNCATE TABLE users", [] as [u32; 0]);
+
+    let table = String::from("sessions");
+
+    // SAFETY: the table name is a constant
+    assert_query(
+        truncate(unsafe { trusted(table) }),
+        "TRUNCATE TABLE sessions",
+        [] as [u32; 0],
+    );
+}
+
+#[test]
+fn query_fields() {
+    let q = (query("SELECT")
+        + fields_aliased([("countries.name", "country"), ("users.id", "id")])
+        + "FROM users"
+        + "JOIN countries ON countries.id = users.country_id")
+        .wh(("users.id = ?", 1));
+
+    assert_query(
+        q,
+        "SELECT countries.name AS country, users.id AS id FROM users JOIN countries ON countries.id = users.country_id WHERE users.id = ?",
+        [1],
+    );
+
+    let q = query("SELECT") + fields(["id", "email"]) + "FROM users";
+
+    assert_query(q, "SELECT id, email FROM users", [] as [u32; 0]);
+}
+
+#[test]
+fn query_indexed() {
+    let q = query("SELECT '?', \"?\" FROM users")
+        .wh(("id = ?", 1))
+        .and("name != 'it''s ?'")
+        .and(("email = ?", "?"));
+
+    assert_eq!(
+        q.build(ArgFormat::Indexed),
+        (
+            String::from(
+                "SELECT '?', \"?\" FROM users WHERE id = $1 AND name != 'it''s ?' AND email = $2"
+            ),
+            vec![Type::Int32(1), Type::String("?".into())],
+        )
+    );
+}
+
+#[test]
+fn query_indexed_dedup() {
+    let q = query("SELECT * FROM events")
+        .wh(("owner_id = ?", 7))
+        .and(expr(("creator_id = ?", 7)).or(("assignee_id = ?", 7)))
+        .and(("status = ?", "open"));
+
+    assert_eq!(
+        q.build(ArgFormat::IndexedDedup),
+        (
+            String::from(
+                "SELECT * FROM events WHERE owner_id = $1 AND (creator_id = $1 OR assignee_id = $1) AND status = $2"
+            ),
+            vec![Type::Int32(7), Type::String("open".into())],
+        )
+    );
+}
+
+#[test]
+fn query_at_indexed() {
+    let q = query("SELECT '?' AS a FROM users")
+        .wh(("id = ?", 1))
+        .and("name != 'it''s ?'")
+        .and(("email = ?", "?"));
+
+    assert_eq!(
+        q.build(ArgFormat::AtIndexed),
+        (
+            String::from(
+                "SELECT '?' AS a FROM users WHERE id = @p1 AND name != 'it''s ?' AND email = @p2"
+            ),
+            vec![Type::Int32(1), Type::String("?".into())],
+        )
+    );
+}
+
+#[test]
+fn query_comment() {
+    let q = query("SELECT * FROM users")
+        .wh(("id = ?", 1))
+        .comment("service:api,route:/users?id");
+
+    assert_eq!(
+        q.build(ArgFormat::Indexed),
+        (
+            String::from("SELECT * FROM users WHERE id = $1 /* service:api,route:/users?id */"),
+            vec![Type::Int32(1)],
+        )
+    );
+
+    let q = query("SELECT 1").comment("*/ DROP TABLE users; /*");
+
+    assert_eq!(q.to_string(), "SELECT 1 /* * / DROP TABLE users; / * */");
+}
+
+#[test]
+fn expr_precedence() {
+    let q = query("SELECT * FROM t").wh(expr(("a = ?", 1))
+        .or(("b = ?", 2))
+        .and(("c = ?", 3))
+        .or(expr(("d = ?", 4)).and(("e = ?", 5))));
+
+    assert_query(
+        q,
+        "SELECT * FROM t WHERE (((a = ? OR b = ?) AND c = ?) OR (d = ? AND e = ?))",
+        [1, 2, 3, 4, 5],
+    );
+
+    let q = query("SELECT * FROM t").wh(expr("a").and("b").group().and("c").xor("d"));
+
+    assert_query(
+        q,
+        "SELECT * FROM t WHERE (((a AND b) AND c) XOR d)",
+        [] as [u32; 0],
+    );
+}
+
+#[test]
+fn query_buffer_append_iter() {
+    let mut values = QueryBuffer::from("VALUES ");
+
+    values
+        .append_iter([
+            vec![Type::Int32(1), "a".into()],
+            vec![2.into(), "b".into()],
+            vec![3.into(), Type::Null],
+        ])
+        .unwrap();
+
+    assert_query(
+        query("INSERT INTO t (id, name)") + values,
+        "INSERT INTO t (id, name) VALUES (?,?),(?,?),(?,?)",
+        [
+            Type::Int32(1),
+            Type::String("a".into()),
+            Type::Int32(2),
+            Type::String("b".into()),
+            Type::Int32(3),
+            Type::Null,
+        ],
+    );
+
+    let mut values = QueryBuffer::from("VALUES ");
+    let result = values.append_iter([vec![1, 2], vec![3]]);
+
+    assert!(matches!(
+        result,
+        Err(esql::Error::RowArity {
+            row: 1,
+            expected: 2,
+            got: 1
+        })
+    ));
+}
+
+#[test]
+fn query_row_locking() {
+    let q = query("SELECT * FROM jobs").wh(("id = ?", 1)).for_update();
+    assert_query(q, "SELECT * FROM jobs WHERE id = ? FOR UPDATE", [1]);
+
+    let q = query("SELECT * FROM jobs").for_share();
+    assert_query(q, "SELECT * FROM jobs FOR SHARE", [] as [u32; 0]);
+
+    let q = (query("SELECT * FROM jobs").wh("state = 'queued'") + ("ORDER BY id LIMIT ?", 10))
+        .for_update()
+        .skip_locked();
+
+    assert_query(
+        q,
+        "SELECT * FROM jobs WHERE state = 'queued' ORDER BY id LIMIT ? FOR UPDATE SKIP LOCKED",
+        [10],
+    );
+
+    let q = query("SELECT * FROM jobs").for_share().nowait();
+    assert_query(q, "SELECT * FROM jobs FOR SHARE NOWAIT", [] as [u32; 0]);
+}
+
+#[test]
+fn query_union() {
+    let active = query("SELECT id FROM users")
+        .wh(("status = ?", "active"))
+        .and(("age >= ?", 18));
+
+    let admins = query("SELECT id FROM admins")
+        .wh(("role = ?", "owner"))
+        .or(("role = ?", "admin"));
+
+    assert_eq!(
+        active.union_query(admins).build(ArgFormat::Indexed),
+        (
+            String::from(
+                "SELECT id FROM users WHERE status = $1 AND age >= $2 UNION SELECT id FROM admins WHERE role = $3 OR role = $4"
+            ),
+            vec![
+                Type::String("active".into()),
+                Type::Int32(18),
+                Type::String("owner".into()),
+                Type::String("admin".into()),
+            ],
+        )
+    );
+
+    let q = query(("SELECT ?", 1)).union_all_query(query(("SELECT ?", 2)));
+
+    assert_query(q, "SELECT ? UNION ALL SELECT ?", [1, 2]);
+}
+
+#[test]
+fn query_any_all() {
+    let q = query("SELECT * FROM posts").wh(any("tag", vec!["rust", "sql"]));
+
+    assert_query(
+        q,
+        "SELECT * FROM posts WHERE tag = ANY(?)",
+        [Type::Array(vec!["rust".into(), "sql".into()])],
+    );
+
+    let q = query("SELECT * FROM posts").wh(any("tag", Vec::<&str>::new()));
+
+    assert_query(
+        q,
+        "SELECT * FROM posts WHERE tag = ANY(?)",
+        [Type::Array(vec![])],
+    );
+
+    let q = query("SELECT * FROM users").wh(all(arg("admin"), ["owner", "admin"]));
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE ? = ALL(?)",
+        [
+            Type::String("admin".into()),
+            Type::Array(vec!["owner".into(), "admin".into()]),
+        ],
+    );
+}
+
+#[test]
+fn nonzero_args() {
+    let id = std::num::NonZeroU32::new(42).unwrap();
+
+    assert_query(
+        query("SELECT * FROM users").wh(("id = ?", id)),
+        "SELECT * FROM users WHERE id = ?",
+        [Type::UInt32(42)],
+    );
+
+    assert_eq!(
+        Type::from(std::num::NonZeroI64::new(-7).unwrap()),
+        Type::Int64(-7)
+    );
+}
+
+#[test]
+fn query_paginate() {
+    let q = query("SELECT * FROM users").paginate(1, 20).unwrap();
+    assert_query(q, "SELECT * FROM users LIMIT ? OFFSET ?", [20i64, 0]);
+
+    let q = (query("SELECT * FROM users").wh(("active = ?", true)) + "ORDER BY id")
+        .paginate(3, 20)
+        .unwrap();
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE active = ? ORDER BY id LIMIT ? OFFSET ?",
+        [Type::Bool(true), Type::Int64(20), Type::Int64(40)],
+    );
+
+    assert!(matches!(
+        query("SELECT * FROM users").paginate(0, 20),
+        Err(esql::Error::InvalidPage {
+            page: 0,
+            per_page: 20
+        })
+    ));
+}
+
+#[test]
+fn query_limit_offset() {
+    let q = query("SELECT * FROM users").limit_offset(10, 30);
+    assert_query(q, "SELECT * FROM users LIMIT ? OFFSET ?", [10i64, 30]);
+
+    let q = query("SELECT * FROM users")
+        .wh(("active = ?", true))
+        .limit_offset_comma(10, 30);
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE active = ? LIMIT ?, ?",
+        [Type::Bool(true), Type::Int64(30), Type::Int64(10)],
+    );
+}
+
+#[test]
+fn text_args() {
+    enum Role {
+        Admin,
+        Guest,
+    }
+
+    impl AsRef<str> for Role {
+        fn as_ref(&self) -> &str {
+            match self {
+                Role::Admin => "admin",
+                Role::Guest => "guest",
+            }
+        }
+    }
+
+    let q =
+        query("SELECT * FROM users").wh(("role IN (?, ?)", text(Role::Admin), text(Role::Guest)));
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE role IN (?, ?)",
+        ["admin", "guest"],
+    );
+}
+
+#[test]
+fn secret_args() {
+    let q = query("SELECT * FROM users").wh(("token = ?", esql::secret("abc123")));
+    let debug = format!("{q:?}");
+
+    assert!(debug.contains("***"));
+    assert!(!debug.contains("abc123"));
+
+    let (_, args) = q.build(ArgFormat::Indexed);
+
+    match &args[0] {
+        Type::Secret(secret) => assert_eq!(secret.expose(), &Type::String("abc123".into())),
+        other => panic!("unexpected arg {other:?}"),
+    }
+}
+
+#[test]
+fn query_rebind() {
+    let template = query("SELECT * FROM users")
+        .wh("name != '?'")
+        .and(("id = ?", 0))
+        .and(("status = ?", ""));
+
+    let q = template
+        .clone()
+        .rebind(vec![Type::Int32(1), "active".into()])
+        .unwrap();
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE name != '?' AND id = ? AND status = ?",
+        [Type::Int32(1), Type::String("active".into())],
+    );
+
+    assert!(matches!(
+        template.rebind(vec![Type::Int32(1)]),
+        Err(esql::Error::ArgCountMismatch {
+            expected: 2,
+            got: 1
+        })
+    ));
+}
+
+#[test]
+fn query_or_where_or_group() {
+    let q = query("SELECT * FROM users")
+        .or_where([("role = ?", "admin"), ("role = ?", "owner")])
+        .and(("active = ?", true));
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE (role = ? OR role = ?) AND active = ?",
+        [
+            Type::String("admin".into()),
+            Type::String("owner".into()),
+            Type::Bool(true),
+        ],
+    );
+
+    let q = query("SELECT * FROM users")
+        .wh(("role = ?", "admin"))
+        .and(("active = ?", true))
+        .or_group([("role = ?", "guest"), ("invited_by = ?", "admin")]);
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE role = ? AND active = ? OR (role = ? AND invited_by = ?)",
+        [
+            Type::String("admin".into()),
+            Type::Bool(true),
+            Type::String("guest".into()),
+            Type::String("admin".into()),
+        ],
+    );
+}
+
+#[test]
+fn in_expr_with_parameterized_subject() {
+    let q = query("SELECT * FROM users").wh(in_expr(("lower(?)", "Name"), ["a", "b"]));
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE lower(?) IN (?,?)",
+        ["Name", "a", "b"],
+    );
+}
+
+#[test]
+fn query_buffer_join_with() {
+    let q = query("SELECT") + QueryBuffer::from("u").join_with("", ".id") + "FROM users u";
+
+    assert_query(q, "SELECT u.id FROM users u", [] as [u32; 0]);
+
+    let q = query("SELECT")
+        + QueryBuffer::from("coalesce(nickname")
+            .join_with(", ", arg("guest"))
+            .join_with("", ")");
+
+    assert_query(q, "SELECT coalesce(nickname, ?)", ["guest"]);
+}
+
+#[test]
+fn quoted_identifiers() {
+    let q = query("SELECT")
+        + esql::ident("order")
+        + "FROM"
+        + esql::ident("user")
+        + "WHERE"
+        + esql::ident(r#"we"ird"#)
+        + ("= ?", 1);
+
+    assert_query(q, r#"SELECT "order" FROM "user" WHERE "we""ird" = ?"#, [1]);
+
+    let q = query("SELECT") + esql::ident_mysql("order") + "FROM" + esql::ident_mysql("we`ird");
+
+    assert_query(q, "SELECT `order` FROM `we``ird`", [] as [u32; 0]);
+}
+
+#[test]
+fn query_with_recursive() {
+    let q = esql::with_recursive(
+        "subordinates",
+        ["id", "manager_id", "depth"],
+        query("SELECT id, manager_id, 0 FROM employees").wh(("id = ?", 7)),
+        query("SELECT e.id, e.manager_id, s.depth + 1 FROM employees e")
+            + "JOIN subordinates s ON e.manager_id = s.id"
+            + ("WHERE s.depth < ?", 3),
+    ) + "SELECT id FROM subordinates";
+
+    assert_query(
+        q,
+        "WITH RECURSIVE subordinates(id, manager_id, depth) AS (\
+         SELECT id, manager_id, 0 FROM employees WHERE id = ? \
+         UNION ALL \
+         SELECT e.id, e.manager_id, s.depth + 1 FROM employees e \
+         JOIN subordinates s ON e.manager_id = s.id WHERE s.depth < ?\
+         ) SELECT id FROM subordinates",
+        [7, 3],
+    );
+}
+
+#[test]
+fn char_args() {
+    assert_eq!(Type::from('x'), Type::String("x".into()));
+
+    assert_query(
+        query("SELECT * FROM grades").wh(("grade = ?", 'ä')),
+        "SELECT * FROM grades WHERE grade = ?",
+        ["ä"],
+    );
+}
+
+#[test]
+fn query_append_if() {
+    let build = |with_team: bool| {
+        query("SELECT * FROM users")
+            .append_if(
+                with_team,
+                (
+                    "JOIN teams ON teams.id = users.team_id AND teams.kind = ?",
+                    "core",
+                ),
+            )
+            .wh(("users.id > ?", 10))
+    };
+
+    let q = build(true);
+    assert_eq!(q.arg_count(), 2);
+    assert_query(
+        q,
+        "SELECT * FROM users JOIN teams ON teams.id = users.team_id AND teams.kind = ? WHERE users.id > ?",
+        [Type::String("core".into()), Type::Int32(10)],
+    );
+
+    let q = build(false);
+    assert_eq!(q.arg_count(), 1);
+    assert_query(q, "SELECT * FROM users WHERE users.id > ?", [10]);
+}
+
+#[test]
+fn query_bind_at() {
+    let q = query("SELECT * FROM users")
+        .wh("created_at > ?")
+        .and(("status = ?", "active"))
+        .bind_at(0, "2024-01-01");
+
+    assert_eq!(
+        q.build(ArgFormat::Indexed),
+        (
+            String::from("SELECT * FROM users WHERE created_at > $1 AND status = $2"),
+            vec![
+                Type::String("2024-01-01".into()),
+                Type::String("active".into())
+            ],
+        )
+    );
+
+    let fragment = QueryBuffer::from("a = ? AND b = ?")
+        .join_with(" AND ", ("c = ?", 3))
+        .bind_at(0, 1)
+        .bind_at(1, 2);
+
+    assert_query(
+        query("SELECT * FROM t").wh(fragment),
+        "SELECT * FROM t WHERE a = ? AND b = ? AND c = ?",
+        [1, 2, 3],
+    );
+}
+
+#[test]
+fn query_wrap_prepend() {
+    let inner = query("SELECT * FROM users")
+        .wh(("age > ?", 18))
+        .and(("country = ?", "DE"));
+
+    let q = inner
+        .wrap(
+            ("SELECT count(*) FILTER (WHERE score > ?) FROM (", 5),
+            ") AS sub",
+        )
+        .wh(("sub.name <> ?", "admin"));
+
+    assert_query(
+        q,
+        "SELECT count(*) FILTER (WHERE score > ?) FROM (SELECT * FROM users WHERE age > ? AND country = ?) AS sub WHERE sub.name <> ?",
+        [
+            Type::Int32(5),
+            Type::Int32(18),
+            Type::String("DE".into()),
+            Type::String("admin".into()),
+        ],
+    );
+
+    let q = query("SELECT * FROM users")
+        .wh(("id = ?", 1))
+        .prepend(("WITH t AS (SELECT ?)", 2))
+        .append_tight(";");
+
+    assert_query(
+        q,
+        "WITH t AS (SELECT ?) SELECT * FROM users WHERE id = ?;",
+        [2, 1],
+    );
+}
+
+#[test]
+fn schema_qualified_table() {
+    let q = query("SELECT * FROM") + esql::table("order", r#"line"items"#) + ("WHERE id = ?", 1);
+
+    assert_query(
+        q,
+        r#"SELECT * FROM "order"."line""items" WHERE id = ?"#,
+        [1],
+    );
+}
+
+#[test]
+fn query_sql_key() {
+    let build = |id: i32, name: &'static str| {
+        query("SELECT * FROM users")
+            .wh(("id = ?", id))
+            .and(("name = ?", name))
+    };
+
+    let (a, b) = (build(1, "a"), build(2, "b"));
+
+    assert_eq!(a.sql_key(), b.sql_key());
+    assert_ne!(
+        a.sql_key(),
+        query("SELECT * FROM users").wh(("id = ?", 1)).sql_key()
+    );
+}
+
+#[test]
+fn query_and_opt_or_opt() {
+    let name = Some("alice");
+    let email: Option<&str> = None;
+
+    let q = query("SELECT * FROM users")
+        .wh(("active = ?", true))
+        .and_opt(name.map(|name| ("name = ?", name)))
+        .and_opt(email.map(|email| ("email = ?", email)))
+        .or_opt(email.map(|email| ("backup_email = ?", email)));
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE active = ? AND name = ?",
+        [Type::Bool(true), Type::String("alice".into())],
+    );
+
+    let q = query("SELECT * FROM users").wh(expr(("a = ?", 1))
+        .or_opt(Some(("b = ?", 2)))
+        .and_opt(None::<&'static str>));
+
+    assert_query(q, "SELECT * FROM users WHERE (a = ? OR b = ?)", [1, 2]);
+}
+
+#[test]
+fn fragment_with_borrowed_args() {
+    let name = String::from("alice");
+    let args = [Type::Int32(1), Type::String(name.as_str().into())];
+
+    let q = query("SELECT * FROM users").wh(("id = ? AND name = ?", &args[..]));
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE id = ? AND name = ?",
+        args.clone(),
+    );
+}
+
+#[test]
+fn unexpected_row_count_display() {
+    let err = esql::Error::UnexpectedRowCount {
+        expected: 1,
+        got: 3,
+        sql: "SELECT id FROM users".into(),
+    };
+
+    assert_eq!(
+        err.to_string(),
+        "query `SELECT id FROM users` returned 3 rows, expected 1"
+    );
+}
+
+#[test]
+fn from_row_error_display() {
+    let err = esql::Error::FromRowError {
+        sql: Some("SELECT id FROM users".into()),
+    };
+
+    assert_eq!(
+        err.to_string(),
+        "conversion from a row of `SELECT id FROM users` failed"
+    );
+
+    let err = esql::Error::FromRowError { sql: None };
+
+    assert_eq!(err.to_string(), "conversion from a row failed");
+}
+
+#[test]
+fn query_into_condition() {
+    let filter = query(("SELECT * FROM users JOIN teams ON teams.org = ?", 1))
+        .wh(("users.age >= ?", 18))
+        .and(("teams.name = ?", "core"))
+        .prepend(("WITH recent AS (SELECT ?)", 2))
+        .into_condition();
+
+    let q = query(("UPDATE users SET seen = ?", true)).wh(filter);
+
+    assert_query(
+        q,
+        "UPDATE users SET seen = ? WHERE users.age >= ? AND teams.name = ?",
+        vec![
+            Type::Bool(true),
+            Type::Int32(18),
+            Type::String(Cow::Borrowed("core")),
+        ],
+    );
+}
+
+#[test]
+fn query_args() {
+    let q = query("SELECT * FROM users")
+        .wh(("id = ?", 1))
+        .and(("password = ?", esql::secret("hunter2")))
+        .and(("name = ?", "alice"));
+
+    let logged: Vec<String> = q.args().iter().map(|arg| format!("{arg:?}")).collect();
+
+    assert_eq!(logged, ["Int32(1)", "Secret(***)", "String(\"alice\")"]);
+    assert_eq!(q.arg_count(), 3);
+}
+
+#[test]
+fn query_from_only() {
+    let q = (query("SELECT * FROM") + esql::only("events")).wh(("created_at < ?", 5));
+
+    assert_query(
+        q,
+        r#"SELECT * FROM ONLY "events" WHERE created_at < ?"#,
+        vec![Type::Int32(5)],
+    );
+}
+
+#[test]
+fn query_and_unique() {
+    let q = query("SELECT * FROM orders")
+        .wh(("status = ?", "open"))
+        .and(("customer_id = ?", 4))
+        .and_unique(("status = ?", "open"))
+        .and_unique(("status = ?", "closed"))
+        .and_unique(("customer_id = ?", 4));
+
+    assert_query(
+        q,
+        "SELECT * FROM orders WHERE status = ? AND customer_id = ? AND status = ?",
+        vec![
+            Type::String(Cow::Borrowed("open")),
+            Type::Int32(4),
+            Type::String(Cow::Borrowed("closed")),
+        ],
+    );
+}
+
+#[test]
+fn query_as_subquery() {
+    let recent = query("SELECT user_id FROM logins")
+        .wh(("at > ?", "2024-01-01"))
+        .and(("country = ?", "DE"));
+
+    let q = query(("SELECT u.name, ? AS source FROM", "logins"))
+        + recent.as_subquery("r")
+        + "JOIN users u ON u.id = r.user_id";
+    let q = q.wh(("u.active = ?", true));
+
+    assert_query(
+        q,
+        "SELECT u.name, ? AS source FROM (SELECT user_id FROM logins WHERE at > ? AND country = ?) AS r \
+         JOIN users u ON u.id = r.user_id WHERE u.active = ?",
+        vec![
+            Type::String(Cow::Borrowed("logins")),
+            Type::String(Cow::Borrowed("2024-01-01")),
+            Type::String(Cow::Borrowed("DE")),
+            Type::Bool(true),
+        ],
+    );
+}
+
+#[test]
+fn expr_and_in() {
+    let e = expr(("team_id = ?", 3))
+        .and_in("role", ["admin", "owner"])
+        .or_in("id", Vec::<i32>::new());
+
+    assert_query(
+        query("SELECT * FROM users").wh(e),
+        "SELECT * FROM users WHERE ((team_id = ? AND role IN (?,?)) OR 1=0)",
+        vec![
+            Type::Int32(3),
+            Type::String(Cow::Borrowed("admin")),
+            Type::String(Cow::Borrowed("owner")),
+        ],
+    );
+}
+
+#[test]
+fn query_arg_format() {
+    let q = query("SELECT * FROM users")
+        .arg_format(ArgFormat::QuestionMark)
+        .wh(("id = ?", 1))
+        .and(("name = ?", "alice"))
+        + "LIMIT 1";
+
+    let (sql, args) = q.clone().build_default();
+
+    assert_eq!(sql, "SELECT * FROM users WHERE id = ? AND name = ? LIMIT 1");
+    assert_eq!(args.len(), 2);
+
+    let (sql, _) = q.arg_format(ArgFormat::AtIndexed).build_default();
+
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE id = @p1 AND name = @p2 LIMIT 1"
+    );
+
+    let (sql, _) = query(("SELECT ?", 1)).build_default();
+
+    assert_eq!(sql, "SELECT $1");
+}
+
+#[test]
+fn query_in_subquery() {
+    let active = query("SELECT id FROM users").wh(("active = ?", true));
+    let banned = query(("SELECT user_id FROM bans WHERE until > ?", 10));
+
+    let q = query("SELECT * FROM orders")
+        .wh(("shop = ?", 1))
+        .and_in_subquery("user_id", active)
+        .and(("total > ?", 100))
+        .and_not_in_subquery("user_id", banned.clone());
+
+    assert_query(
+        q,
+        "SELECT * FROM orders WHERE shop = ? AND user_id IN (SELECT id FROM users WHERE active = ?) \
+         AND total > ? AND user_id NOT IN (SELECT user_id FROM bans WHERE until > ?)",
+        vec![
+            Type::Int32(1),
+            Type::Bool(true),
+            Type::Int32(100),
+            Type::Int32(10),
+        ],
+    );
+
+    let e = expr(("total > ?", 100)).and_not_in_subquery("user_id", banned);
+
+    assert_query(
+        query("SELECT * FROM orders").wh(e),
+        "SELECT * FROM orders WHERE (total > ? AND user_id NOT IN \
+         (SELECT user_id FROM bans WHERE until > ?))",
+        vec![Type::Int32(100), Type::Int32(10)],
+    );
+}
+
+#[test]
+fn escaped_placeholder() {
+    let q = query("SELECT * FROM docs")
+        .wh(("data ?? 'tags' AND id = ?", 1))
+        .and("a ?? b");
+
+    assert_eq!(
+        q.clone().build(ArgFormat::QuestionMark),
+        (
+            String::from("SELECT * FROM docs WHERE data ? 'tags' AND id = ? AND a ? b"),
+            vec![Type::Int32(1)]
+        )
+    );
+    assert_eq!(
+        q.clone().build(ArgFormat::Indexed).0,
+        "SELECT * FROM docs WHERE data ? 'tags' AND id = $1 AND a ? b"
+    );
+    assert!(q.rebind(vec![Type::Int32(2)]).is_ok());
+}
+
+#[test]
+fn query_build_to() {
+    let mut sql = String::from("leftover");
+
+    let first = query("SELECT * FROM users").wh(("id = ?", 1));
+    let args = first.build_to(&mut sql, ArgFormat::Indexed);
+
+    assert_eq!(sql, "SELECT * FROM users WHERE id = $1");
+    assert_eq!(args, [Type::Int32(1)]);
+
+    let second = query("SELECT * FROM teams")
+        .wh(("a = ?", 2))
+        .and(("b = ?", 2));
+    let args = second.build_to(&mut sql, ArgFormat::IndexedDedup);
+
+    assert_eq!(sql, "SELECT * FROM teams WHERE a = $1 AND b = $1");
+    assert_eq!(args, [Type::Int32(2)]);
+    assert_eq!(second.arg_count(), 2);
+}
+
+#[test]
+fn raw_statement() {
+    let (sql, args) = esql::raw((
+        "INSERT INTO users (name, age) VALUES (?, ?) ON CONFLICT DO NOTHING",
+        "alice",
+        30,
+    ))
+    .build(ArgFormat::Indexed);
+
+    assert_eq!(
+        sql,
+        "INSERT INTO users (name, age) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+    );
+    assert_eq!(
+        args,
+        [Type::String(Cow::Borrowed("alice")), Type::Int32(30)]
+    );
+}
+
+#[test]
+fn select_from() {
+    let q = esql::select(["id", "email"])
+        .from("users")
+        .wh(("id = ?", 4))
+        .and("deleted_at IS NULL");
+
+    assert_query(
+        q,
+        "SELECT id, email FROM users WHERE id = ? AND deleted_at IS NULL",
+        vec![Type::Int32(4)],
+    );
+
+    let q = esql::select(fields_aliased([("u.id", "id"), ("t.name", "team")]))
+        .from_only("users")
+        .wh(("age > ?", 18));
+
+    assert_query(
+        q,
+        r#"SELECT u.id AS id, t.name AS team FROM ONLY "users" WHERE age > ?"#,
+        vec![Type::Int32(18)],
+    );
+}
+
+#[test]
+fn aggregate_filter() {
+    let q = query("SELECT team_id,")
+        + esql::filter(("sum(points * ?)", 2), ("status = ?", "done"))
+        + "AS score FROM tasks";
+    let q = q.wh(("created_at > ?", 10)) + "GROUP BY team_id";
+
+    assert_query(
+        q,
+        "SELECT team_id, sum(points * ?) FILTER (WHERE status = ?) AS score FROM tasks \
+         WHERE created_at > ? GROUP BY team_id",
+        vec![
+            Type::Int32(2),
+            Type::String(Cow::Borrowed("done")),
+            Type::Int32(10),
+        ],
+    );
+}
+
+#[test]
+fn query_build_named() {
+    let (sql, args) = query("SELECT * FROM users")
+        .wh(("name = ? AND note <> '?'", "alice"))
+        .and(("age > ?", 18))
+        .build_named();
+
+    assert_eq!(
+        sql,
+        "SELECT * FROM users WHERE name = :p0 AND note <> '?' AND age > :p1"
+    );
+    assert_eq!(
+        args,
+        HashMap::from([
+            (String::from("p0"), Type::String(Cow::Borrowed("alice"))),
+            (String::from("p1"), Type::Int32(18)),
+        ])
+    );
+}
+
+#[test]
+fn query_validate() {
+    let balanced = query("SELECT * FROM users")
+        .wh(expr("a = ')'").or("b IN (1, 2)"))
+        .comment("(unbalanced");
+
+    assert!(balanced.validate().is_ok());
+
+    let unclosed = query("SELECT count(* FROM users").wh("(a OR b)");
+
+    assert!(matches!(
+        unclosed.validate(),
+        Err(esql::Error::UnbalancedParentheses(12))
+    ));
+
+    let unopened = query("SELECT \"(\" FROM t").wh("a OR b)");
+
+    assert!(matches!(
+        unopened.validate(),
+        Err(esql::Error::UnbalancedParentheses(30))
+    ));
+}
+
+#[test]
+fn query_union_all_iter() {
+    let q = Query::union_all_iter(["a", "b", "c"].into_iter().enumerate().map(|(i, name)| {
+        query((
+            "SELECT id FROM items WHERE name = ? AND rank > ?",
+            name,
+            i as i32,
+        ))
+    }));
+
+    assert_eq!(
+        q.build(ArgFormat::Indexed),
+        (
+            "SELECT id FROM items WHERE name = $1 AND rank > $2 \
+             UNION ALL SELECT id FROM items WHERE name = $3 AND rank > $4 \
+             UNION ALL SELECT id FROM items WHERE name = $5 AND rank > $6"
+                .to_owned(),
+            vec![
+                Type::String(Cow::Borrowed("a")),
+                Type::Int32(0),
+                Type::String(Cow::Borrowed("b")),
+                Type::Int32(1),
+                Type::String(Cow::Borrowed("c")),
+                Type::Int32(2),
+            ],
+        )
+    );
+
+    let q = Query::union_all_iter([query(("SELECT ?", 1))]);
+    assert_query(q, "SELECT ?", [1]);
+
+    let q = Query::union_all_iter((0..0).map(|i| query(("SELECT ?", i))));
+    assert_query(q, "SELECT WHERE false", Vec::<Type>::new());
+}
+
+#[test]
+fn query_tablesample() {
+    let q = query("SELECT * FROM events")
+        .tablesample("BERNOULLI", 2.5)
+        .wh(("kind = ?", "click"));
+
+    assert_query(
+        q,
+        "SELECT * FROM events TABLESAMPLE BERNOULLI (?) WHERE kind = ?",
+        [Type::Float(2.5), Type::String(Cow::Borrowed("click"))],
+    );
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn uuid_reference_args() {
+    let id = uuid::Uuid::from_u128(42);
+    let q = query("SELECT * FROM users").wh(("id = ?", &id));
+
+    assert_query(q, "SELECT * FROM users WHERE id = ?", [Type::Uuid(id)]);
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn time_reference_args() {
+    let since = time::OffsetDateTime::UNIX_EPOCH;
+    let grace = time::Duration::minutes(5);
+    let q = query("SELECT * FROM sessions").wh(("created_at > ? - ?", &since, &grace));
+
+    assert_query(
+        q,
+        "SELECT * FROM sessions WHERE created_at > ? - ?",
+        [Type::OffsetDateTime(since), Type::Duration(grace)],
+    );
+}
+
+#[test]
+fn query_without_limit() {
+    let q = query("SELECT * FROM users")
+        .wh(("role = ?", "admin"))
+        .limit_offset_comma(10, 20)
+        .prepend(("/* ? */ WITH x AS (SELECT ?)", 1))
+        .for_update();
+
+    assert_query(
+        q.without_limit(),
+        "/* ? */ WITH x AS (SELECT ?) SELECT * FROM users WHERE role = ?",
+        [Type::Int32(1), Type::String(Cow::Borrowed("admin"))],
+    );
+
+    let q = query("SELECT * FROM users LIMIT ?").bind_at(0, 5);
+    assert_query(q.without_limit(), "SELECT * FROM users LIMIT ?", [5]);
+}
+
+#[test]
+fn query_filtered() {
+    let status = Some("active");
+    let country: Option<&str> = None;
+    let min_age = Some(18);
+
+    let q = query("SELECT * FROM users").wh_all([
+        status.map(|status| expr(("status = ?", status))),
+        country.map(|country| expr(("country = ?", country))),
+        min_age.map(|age| expr(("age >= ?", age))),
+    ]);
+
+    assert_eq!(
+        q.build(ArgFormat::QuestionMark),
+        (
+            String::from("SELECT * FROM users WHERE 1=1 AND (status = ?) AND (age >= ?)"),
+            vec![Type::String("active".into()), Type::Int32(18)],
+        )
+    );
+
+    let mut q = query("SELECT * FROM users").filtered();
+
+    if let Some(status) = status {
+        q = q.and(("status = ?", status));
+    }
+
+    if let Some(country) = country {
+        q = q.and(("country = ?", country));
+    }
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE 1=1 AND status = ?",
+        ["active"],
+    );
+}
+
+#[test]
+fn query_all_of_any_of() {
+    let q = query("SELECT * FROM users")
+        .wh(all_of([] as [QueryBuffer; 0]))
+        .and(any_of([] as [QueryBuffer; 0]));
+
+    assert_query(q, "SELECT * FROM users WHERE 1=1 AND 1=0", [] as [u32; 0]);
+
+    let q = query("SELECT * FROM users")
+        .wh(all_of([("a = ?", 1)]))
+        .and(any_of([("b = ?", 2)]));
+
+    assert_query(q, "SELECT * FROM users WHERE (a = ?) AND (b = ?)", [1, 2]);
+
+    let roles = vec![("role = ?", 3), ("role = ?", 4), ("role = ?", 5)];
+
+    let q = query("SELECT * FROM users")
+        .wh(all_of([expr(("a = ?", 1)), expr(("b = ?", 2))]))
+        .and(any_of(roles));
+
+    assert_query(
+        q,
+        "SELECT * FROM users WHERE ((a = ?) AND (b = ?)) AND (role = ? OR role = ? OR role = ?)",
+        [1, 2, 3, 4, 5],
+    );
+}
+
+#[test]
+fn query_large_string_args_are_not_copied() {
+    let borrowed = "x".repeat(1024 * 1024);
+    let owned = "y".repeat(1024 * 1024);
+    let owned_ptr = owned.as_ptr();
+
+    let (_, args) = query("SELECT * FROM documents")
+        .wh(("a = ?", borrowed.as_str()))
+        .and(("b = ?", owned))
+        .build(ArgFormat::Indexed);
+
+    match args.as_slice() {
+        [Type::String(Cow::Borrowed(a)), Type::String(Cow::Owned(b))] => {
+            assert_eq!(a.as_ptr(), borrowed.as_ptr());
+            assert_eq!(b.as_ptr(), owned_ptr);
+        }
+        args => panic!("unexpected args: {} items", args.len()),
+    }
+}
+
+#[test]
+fn query_introspection() {
+    let q = query("SELECT * FROM users")
+        .wh(("a = ?", 1))
+        .and(in_expr("b", [2, 3, 4]));
+
+    assert_eq!(q.sql(), "SELECT * FROM users WHERE a = ? AND b IN (?,?,?)");
+    assert_eq!(q.sql_len(), 48);
+    assert_eq!(q.arg_count(), 4);
+
+    let q = query("SELECT 1");
+
+    assert_eq!(q.arg_count(), 0);
+}
+
+#[test]
+fn reused_expr() {
+    let condition = expr(("a = ?", 1)).or(("b = ?", 2));
+
+    assert_query(
+        query("SELECT * FROM t").wh_expr(condition.clone()),
+        "SELECT * FROM t WHERE (a = ? OR b = ?)",
+        [1, 2],
+    );
+
+    assert_query(
+        query("SELECT a, b FROM t GROUP BY a, b").having_expr(condition),
+        "SELECT a, b FROM t GROUP BY a, b HAVING (a = ? OR b = ?)",
+        [1, 2],
+    );
+}
+
+#[test]
+fn distinct_from() {
+    assert_query(
+        query("SELECT * FROM t")
+            .wh(is_distinct_from("a", 1))
+            .or(is_not_distinct_from("b", None::<i32>)),
+        "SELECT * FROM t WHERE a IS DISTINCT FROM ? OR b IS NOT DISTINCT FROM ?",
+        [Type::Int32(1), Type::Null],
+    );
+
+    assert_query(
+        query("SELECT a, count(*) FROM t GROUP BY a")
+            .having(expr("count(*) > 1").and(is_not_distinct_from("a", "x"))),
+        "SELECT a, count(*) FROM t GROUP BY a HAVING (count(*) > 1 AND a IS NOT DISTINCT FROM ?)",
+        ["x"],
+    );
+}
+
 fn assert_query<'a, S>(
     query: Query<'a, S>,
     expected_query: &str,
@@ -0,0 +1,6 @@
+#[test]
+fn typestate() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/typestate/pass/*.rs");
+    t.compile_fail("tests/typestate/fail/*.rs");
+}
@@ -0,0 +1,5 @@
+use esql::query;
+
+fn main() {
+    let _ = query("SELECT * FROM users").and(("id = ?", 1));
+}
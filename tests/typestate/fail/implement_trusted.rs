@@ -0,0 +1,13 @@
+use std::fmt;
+
+struct UserInput(String);
+
+impl fmt::Display for UserInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl esql::Trusted for UserInput {}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use esql::query;
+
+fn main() {
+    let _ = query("SELECT * FROM users")
+        .having("count(*) > 1")
+        .or(("id = ?", 1));
+}
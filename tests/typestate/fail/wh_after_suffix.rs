@@ -0,0 +1,5 @@
+use esql::query;
+
+fn main() {
+    let _ = (query("SELECT * FROM users").wh(("id = ?", 1)) + "ORDER BY id").and("a = 1");
+}
@@ -0,0 +1,7 @@
+use esql::query;
+
+fn main() {
+    let _ = query("SELECT * FROM users")
+        .wh(("id = ?", 1))
+        .wh(("email = ?", "foo@example.com"));
+}
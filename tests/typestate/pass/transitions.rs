@@ -0,0 +1,15 @@
+use esql::{expr, query};
+
+fn main() {
+    let _ = query("SELECT * FROM users")
+        .wh(("a = ?", 1))
+        .and(("b = ?", 2))
+        .or(expr(("c = ?", 3)).and("d = 4"))
+        .having("count(*) > 1")
+        + "ORDER BY a"
+        + "LIMIT 10";
+
+    let _ = (query("SELECT country, count(*) FROM users") + "GROUP BY country")
+        .having(("count(*) > ?", 10))
+        + "ORDER BY country";
+}
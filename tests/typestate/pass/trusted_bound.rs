@@ -0,0 +1,13 @@
+use esql::{query, table, Trusted};
+
+fn count_rows(table_name: impl Trusted) -> String {
+    (query("SELECT count(*) FROM") + table_name).to_string()
+}
+
+fn main() {
+    assert_eq!(count_rows("users"), "SELECT count(*) FROM users");
+    assert_eq!(
+        count_rows(table("audit", "events")),
+        r#"SELECT count(*) FROM "audit"."events""#
+    );
+}